@@ -0,0 +1,319 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use humansize::{format_size, DECIMAL};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::task;
+
+use crate::{
+    config::BandwidthRule,
+    session::{interruptible_sleep, ResumeWatch},
+    utils::execute_command,
+};
+
+const NET_DIR: &str = "/sys/class/net";
+const USAGE_DIR_NAME: &str = "alertify";
+const USAGE_FILE_NAME: &str = "bandwidth.toml";
+
+pub const DIRECTIONS: &[&str] = &["rx", "tx", "total"];
+pub const PERIODS: &[&str] = &["daily", "monthly"];
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Counters {
+    rx: u64,
+    tx: u64,
+}
+
+/// Traffic accounted to the current day and month of an interface.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct Usage {
+    day: String,
+    day_rx: u64,
+    day_tx: u64,
+    month: String,
+    month_rx: u64,
+    month_tx: u64,
+    /// Quota alerts already sent as `{rule index}-{period}`, kept across
+    /// restarts so that an alert fires once per period
+    #[serde(default)]
+    notified: BTreeSet<String>,
+}
+
+impl Usage {
+    fn add(&mut self, now: &DateTime<Local>, rx: u64, tx: u64) {
+        let day = now.format("%Y-%m-%d").to_string();
+        let month = now.format("%Y-%m").to_string();
+
+        if self.day != day {
+            self.day = day;
+            self.day_rx = 0;
+            self.day_tx = 0;
+        }
+        if self.month != month {
+            self.month = month;
+            self.month_rx = 0;
+            self.month_tx = 0;
+        }
+
+        let (day, month) = (&self.day, &self.month);
+        self.notified.retain(|key| key.ends_with(day.as_str()) || key.ends_with(month.as_str()));
+
+        self.day_rx += rx;
+        self.day_tx += tx;
+        self.month_rx += rx;
+        self.month_tx += tx;
+    }
+
+    fn period(&self, period: &str) -> (&str, Counters) {
+        match period {
+            "monthly" => (&self.month, Counters { rx: self.month_rx, tx: self.month_tx }),
+            _         => (&self.day,   Counters { rx: self.day_rx,   tx: self.day_tx }),
+        }
+    }
+
+    /// Records a quota alert for the current period, returns `false` when it
+    /// was already sent.
+    fn notify_quota(&mut self, index: usize, period: &str) -> bool {
+        let key = format!("{}-{}", index, self.period(period).0);
+        self.notified.insert(key)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsageFile {
+    #[serde(default)]
+    interfaces: HashMap<String, Usage>,
+}
+
+fn usage_path() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join(USAGE_DIR_NAME).join(USAGE_FILE_NAME))
+}
+
+fn load_usage(path: &Path) -> HashMap<String, Usage> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return HashMap::new();
+    };
+
+    match toml::from_str::<UsageFile>(&content) {
+        Ok(file) => file.interfaces,
+        Err(e) => {
+            warn!("Failed to parse bandwidth usage file {}: {}", path.display(), e);
+            HashMap::new()
+        }
+    }
+}
+
+fn save_usage(path: &Path, interfaces: &HashMap<String, Usage>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    // the file is rewritten often, replace it atomically so that a kill in
+    // the middle of a write doesn't lose the accounted traffic
+    let file = UsageFile { interfaces: interfaces.clone() };
+    let tmp_path = path.with_extension("toml.tmp");
+    fs::write(&tmp_path, toml::to_string(&file)?)
+        .with_context(|| format!("Failed to write bandwidth usage to {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+
+    Ok(())
+}
+
+fn read_counter(path: &Path, name: &str) -> Option<u64> {
+    fs::read_to_string(path.join("statistics").join(name))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+fn read_counters(dir: &Path) -> HashMap<String, Counters> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return HashMap::new();
+    };
+
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name() != "lo")
+        .filter_map(|entry| {
+            let path = entry.path();
+            let counters = Counters {
+                rx: read_counter(&path, "rx_bytes")?,
+                tx: read_counter(&path, "tx_bytes")?,
+            };
+            Some((entry.file_name().to_string_lossy().into_owned(), counters))
+        })
+        .collect()
+}
+
+fn select(direction: &str, rx: u64, tx: u64) -> u64 {
+    match direction {
+        "rx" => rx,
+        "tx" => tx,
+        _    => rx + tx,
+    }
+}
+
+pub async fn monitor_bandwidth(rules: Vec<BandwidthRule>, sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let net_dir = Path::new(NET_DIR);
+    let path = usage_path();
+    let mut usage = path.as_deref().map(load_usage).unwrap_or_default();
+
+    let mut previous = read_counters(net_dir);
+    let mut previous_at = Instant::now();
+    let mut above_since: HashMap<(usize, String), Instant> = HashMap::new();
+    let mut resume = ResumeWatch::new();
+
+    loop {
+        interruptible_sleep(Duration::from_secs(10)).await;
+
+        // a rate has to be exceeded for the whole duration while awake
+        if resume.resumed() {
+            above_since.clear();
+        }
+
+        let current = read_counters(net_dir);
+        let now = Instant::now();
+        let elapsed = now.duration_since(previous_at).as_secs_f64().max(1.0);
+        let local_now = Local::now();
+        let mut changed = false;
+
+        for (interface, counters) in &current {
+            // counters start over when the interface is recreated
+            let delta = match previous.get(interface) {
+                Some(prev) => Counters {
+                    rx: counters.rx.checked_sub(prev.rx).unwrap_or(counters.rx),
+                    tx: counters.tx.checked_sub(prev.tx).unwrap_or(counters.tx),
+                },
+                None => Counters::default(),
+            };
+
+            let interface_usage = usage.entry(interface.clone()).or_default();
+            interface_usage.add(&local_now, delta.rx, delta.tx);
+            changed |= delta.rx > 0 || delta.tx > 0;
+
+            let rx_rate = (delta.rx as f64 / elapsed) as u64;
+            let tx_rate = (delta.tx as f64 / elapsed) as u64;
+
+            for (index, rule) in rules.iter().enumerate()
+                .filter(|(_, rule)| rule.interface.as_ref().is_none_or(|matcher| matcher.matches(interface)))
+            {
+                let (_, period_usage) = interface_usage.period(&rule.period);
+                let used = select(&rule.direction, period_usage.rx, period_usage.tx);
+                let rate = select(&rule.direction, rx_rate, tx_rate);
+
+                let should_notify = if let Some(limit) = rule.rate {
+                    let key = format!("bandwidth-rate-{}-{}", index, interface);
+                    let mut sent_guard = sent.lock().unwrap();
+
+                    if rate >= limit {
+                        let since = *above_since.entry((index, interface.clone())).or_insert(now);
+                        now.duration_since(since) >= Duration::from_secs(rule.duration)
+                            && sent_guard.insert(key)
+                    } else {
+                        above_since.remove(&(index, interface.clone()));
+                        sent_guard.remove(&key);
+                        false
+                    }
+                } else if let Some(quota) = rule.quota {
+                    // the period is part of the key, so the alert re-arms with the next period
+                    let notify = used >= quota && interface_usage.notify_quota(index, &rule.period);
+                    changed |= notify;
+                    notify
+                } else {
+                    false
+                };
+
+                if should_notify {
+                    let quota = rule.quota.unwrap_or_default();
+
+                    let mut fields = HashMap::new();
+                    fields.insert("interface",     interface.clone());
+                    fields.insert("direction",     rule.direction.clone());
+                    fields.insert("period",        rule.period.clone());
+                    fields.insert("rate_bytes",    rate.to_string());
+                    fields.insert("rate",          format!("{}/s", format_size(rate, DECIMAL)));
+                    fields.insert("rx_rate_bytes", rx_rate.to_string());
+                    fields.insert("rx_rate",       format!("{}/s", format_size(rx_rate, DECIMAL)));
+                    fields.insert("tx_rate_bytes", tx_rate.to_string());
+                    fields.insert("tx_rate",       format!("{}/s", format_size(tx_rate, DECIMAL)));
+                    fields.insert("used_bytes",    used.to_string());
+                    fields.insert("used",          format_size(used, DECIMAL));
+                    fields.insert("quota_bytes",   quota.to_string());
+                    fields.insert("quota",         format_size(quota, DECIMAL));
+                    fields.insert("left_bytes",    quota.saturating_sub(used).to_string());
+                    fields.insert("left",          format_size(quota.saturating_sub(used), DECIMAL));
+
+                    let rule_clone = rule.clone();
+                    let _ = execute_command(rule_clone.message.exec.as_ref());
+                    task::spawn_blocking(move || {
+                        let _ = rule_clone.message.notify(&fields);
+                    })
+                    .await?;
+                }
+            }
+        }
+
+        // there is no shutdown hook, so save on every tick with traffic to
+        // lose at most one interval on restart
+        if let Some(path) = path.as_ref().filter(|_| changed) {
+            if let Err(e) = save_usage(path, &usage) {
+                warn!("{:#}", e);
+            }
+        }
+
+        previous = current;
+        previous_at = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, day, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn quota_alert_fires_once_per_period() {
+        let mut usage = Usage::default();
+        usage.add(&at(17), 100, 0);
+        assert!(usage.notify_quota(0, "daily"));
+        assert!(usage.notify_quota(0, "monthly"));
+        assert!(!usage.notify_quota(0, "daily"));
+        assert!(usage.notify_quota(1, "daily"));
+
+        // survives a restart through the usage file
+        let file = UsageFile { interfaces: HashMap::from([("wlan0".to_string(), usage)]) };
+        let mut usage = toml::from_str::<UsageFile>(&toml::to_string(&file).unwrap())
+            .unwrap()
+            .interfaces
+            .remove("wlan0")
+            .unwrap();
+        assert!(!usage.notify_quota(0, "daily"));
+
+        // the next day re-arms daily alerts only
+        usage.add(&at(18), 100, 0);
+        assert_eq!(usage.period("daily").1.rx, 100);
+        assert_eq!(usage.period("monthly").1.rx, 200);
+        assert!(!usage.notify_quota(0, "monthly"));
+        assert!(usage.notify_quota(0, "daily"));
+    }
+}
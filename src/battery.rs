@@ -1,26 +1,143 @@
-use anyhow::Result;
-use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}, time::Duration};
-use tokio::{task, time::sleep};
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use std::{collections::{HashMap, HashSet}, fs, path::Path, sync::{Arc, Mutex}, time::Duration};
+use tokio::task;
 use zbus::fdo::PropertiesProxy;
 use zbus_names::InterfaceName;
 
-use crate::{config::BatteryRule, utils::execute_command};
+use crate::{config::{BatteryBackendKind, BatteryRule}, session::interruptible_sleep, utils::execute_command};
 
-pub async fn monitor_battery(rules: Vec<BatteryRule>, sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
-    let conn = zbus::Connection::system().await?;
-    let properties = PropertiesProxy::builder(&conn)
-        .destination("org.freedesktop.UPower")?
-        .path("/org/freedesktop/UPower/devices/DisplayDevice")?
-        .build()
-        .await?;
-    let interface = InterfaceName::try_from("org.freedesktop.UPower.Device")?;
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+pub enum BatteryBackend {
+    UPower {
+        properties: PropertiesProxy<'static>,
+        interface: InterfaceName<'static>,
+    },
+    Sysfs,
+}
+
+impl BatteryBackend {
+    pub async fn new(kind: BatteryBackendKind) -> Result<Self> {
+        match kind {
+            BatteryBackendKind::UPower => Self::upower().await,
+            BatteryBackendKind::Sysfs  => Ok(Self::Sysfs),
+            BatteryBackendKind::Auto   => {
+                match Self::upower().await {
+                    Ok(backend) => Ok(backend),
+                    Err(e) => {
+                        warn!("UPower is not available ({}), falling back to sysfs battery backend", e);
+                        Ok(Self::Sysfs)
+                    }
+                }
+            }
+        }
+    }
+
+    async fn upower() -> Result<Self> {
+        let conn = zbus::Connection::system().await?;
+        let properties = PropertiesProxy::builder(&conn)
+            .destination("org.freedesktop.UPower")?
+            .path("/org/freedesktop/UPower/devices/DisplayDevice")?
+            .build()
+            .await?;
+        let interface = InterfaceName::try_from("org.freedesktop.UPower.Device")?;
+
+        let backend = Self::UPower { properties, interface };
+        // probe once so that a missing UPower service is detected at startup
+        backend.percentage().await?;
+
+        info!("Using UPower battery backend");
+        Ok(backend)
+    }
+
+    pub async fn percentage(&self) -> Result<f64> {
+        match self {
+            Self::UPower { properties, interface } => {
+                let value = properties
+                    .get(interface.clone(), "Percentage")
+                    .await?
+                    .downcast_ref::<f64>()?
+                    .to_owned();
+                Ok(value)
+            }
+            Self::Sysfs => read_sysfs_percentage(Path::new(POWER_SUPPLY_DIR)),
+        }
+    }
+}
+
+fn read_sysfs_attr(path: &Path, attr: &str) -> Option<String> {
+    fs::read_to_string(path.join(attr))
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+fn read_sysfs_number(path: &Path, attr: &str) -> Option<f64> {
+    read_sysfs_attr(path, attr).and_then(|s| s.parse().ok())
+}
+
+/// Combined charge of all system batteries, weighted by their capacity when
+/// the kernel reports energy or charge counters.
+fn read_sysfs_percentage(dir: &Path) -> Result<f64> {
+    let mut now_total  = 0.0;
+    let mut full_total = 0.0;
+    let mut percents   = Vec::new();
+    let mut weighted   = true;
+
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if read_sysfs_attr(&path, "type").as_deref() != Some("Battery") {
+            continue;
+        }
+        // batteries of peripherals (mice, headsets) report scope "Device"
+        if read_sysfs_attr(&path, "scope").as_deref() == Some("Device") {
+            continue;
+        }
+
+        let counters = read_sysfs_number(&path, "energy_now")
+            .zip(read_sysfs_number(&path, "energy_full"))
+            .or_else(|| {
+                read_sysfs_number(&path, "charge_now")
+                    .zip(read_sysfs_number(&path, "charge_full"))
+            });
+
+        match counters {
+            Some((now, full)) if full > 0.0 => {
+                now_total  += now;
+                full_total += full;
+                percents.push(now / full * 100.0);
+            }
+            _ => {
+                if let Some(capacity) = read_sysfs_number(&path, "capacity") {
+                    percents.push(capacity);
+                    weighted = false;
+                }
+            }
+        }
+    }
+
+    if percents.is_empty() {
+        bail!("No batteries found in {}", dir.display());
+    }
+
+    let value = if weighted && full_total > 0.0 {
+        now_total / full_total * 100.0
+    } else {
+        percents.iter().sum::<f64>() / percents.len() as f64
+    };
+
+    Ok(value.clamp(0.0, 100.0))
+}
+
+pub async fn monitor_battery(backend: BatteryBackendKind, rules: Vec<BatteryRule>, sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    let backend = BatteryBackend::new(backend).await?;
 
     loop {
-        let value = properties
-            .get(interface.clone(), "Percentage")
-            .await?
-            .downcast_ref::<f64>()?
-            .to_owned();
+        let value = backend.percentage().await?;
 
         for rule in &rules {
             let should_notify = {
@@ -55,6 +172,58 @@ pub async fn monitor_battery(rules: Vec<BatteryRule>, sent: Arc<Mutex<HashSet<St
             }
         }
 
-        sleep(Duration::from_secs(10)).await;
+        interruptible_sleep(Duration::from_secs(10)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supply(dir: &Path, name: &str, attrs: &[(&str, &str)]) {
+        let path = dir.join(name);
+        fs::create_dir(&path).unwrap();
+        for (attr, value) in attrs {
+            fs::write(path.join(attr), format!("{}\n", value)).unwrap();
+        }
+    }
+
+    #[test]
+    fn energy_counters_weight_by_capacity() {
+        let dir = tempfile::tempdir().unwrap();
+        supply(dir.path(), "BAT0", &[("type", "Battery"), ("energy_now", "10000000"), ("energy_full", "50000000")]);
+        supply(dir.path(), "BAT1", &[("type", "Battery"), ("energy_now", "15000000"), ("energy_full", "25000000")]);
+        supply(dir.path(), "AC", &[("type", "Mains"), ("online", "1")]);
+
+        // 25 of 75 Wh, not the average of 20% and 60%
+        let percentage = read_sysfs_percentage(dir.path()).unwrap();
+        assert!((percentage - 100.0 / 3.0).abs() < 1e-9, "{}", percentage);
+    }
+
+    #[test]
+    fn charge_counters() {
+        let dir = tempfile::tempdir().unwrap();
+        supply(dir.path(), "BAT0", &[("type", "Battery"), ("charge_now", "3000000"), ("charge_full", "4000000"), ("capacity", "70")]);
+
+        assert_eq!(read_sysfs_percentage(dir.path()).unwrap(), 75.0);
+    }
+
+    #[test]
+    fn capacity_only() {
+        let dir = tempfile::tempdir().unwrap();
+        supply(dir.path(), "BAT0", &[("type", "Battery"), ("capacity", "40")]);
+        supply(dir.path(), "BAT1", &[("type", "Battery"), ("energy_now", "0"), ("energy_full", "0"), ("capacity", "90")]);
+
+        assert_eq!(read_sysfs_percentage(dir.path()).unwrap(), 65.0);
+    }
+
+    #[test]
+    fn peripheral_batteries_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        supply(dir.path(), "hidpp_battery_0", &[("type", "Battery"), ("scope", "Device"), ("capacity", "5")]);
+        assert!(read_sysfs_percentage(dir.path()).is_err());
+
+        supply(dir.path(), "BAT0", &[("type", "Battery"), ("scope", "System"), ("capacity", "80")]);
+        assert_eq!(read_sysfs_percentage(dir.path()).unwrap(), 80.0);
     }
 }
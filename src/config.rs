@@ -1,9 +1,14 @@
 use anyhow::{Context, Result};
 use serde::Deserialize;
-use std::{env, fs, path::{Path, PathBuf}};
+use std::{collections::HashMap, env, fs, path::{Path, PathBuf}};
 use log::info;
 
-use crate::message::Message;
+use crate::{
+    bandwidth, cpu, display, kmsg,
+    matcher::{self, Matcher},
+    message::Message,
+    netlink, network, oom, probe, process, session, systemd, temperature,
+};
 
 const DEFAULT_CONFIG: &str = include_str!("../config.example.toml");
 const CONFIG_FILE_NAME: &str = "config.toml";
@@ -11,6 +16,11 @@ const CONFIG_DIR_NAME: &str = "alertify";
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
+    #[serde(default)]
+    pub battery_backend: BatteryBackendKind,
+    /// udev subsystems to subscribe to, derived from the rules when unset
+    #[serde(default)]
+    pub udev_subsystems: Option<Vec<String>>,
     #[serde(default)]
     pub battery: Vec<BatteryRule>,
     #[serde(default)]
@@ -23,21 +33,75 @@ pub struct Config {
     pub storage: Vec<StorageRule>,
     #[serde(default)]
     pub device: Vec<DeviceRule>,
+    #[serde(default)]
+    pub network: Vec<NetworkRule>,
+    #[serde(default)]
+    pub wifi: Vec<WifiRule>,
+    #[serde(default)]
+    pub link: Vec<LinkRule>,
+    #[serde(default)]
+    pub bandwidth: Vec<BandwidthRule>,
+    #[serde(default)]
+    pub probe: Vec<ProbeRule>,
+    #[serde(default)]
+    pub temperature: Vec<TemperatureRule>,
+    #[serde(default)]
+    pub fan: Vec<FanRule>,
+    #[serde(default)]
+    pub process: Vec<ProcessRule>,
+    #[serde(default)]
+    pub oom: Vec<OomRule>,
+    #[serde(default)]
+    pub kernel_log: Vec<KernelLogRule>,
+    #[serde(default)]
+    pub journal: Vec<JournalRule>,
+    #[serde(default)]
+    pub systemd_unit: Vec<SystemdUnitRule>,
+    #[serde(default)]
+    pub session: Vec<SessionRule>,
+    #[serde(default)]
+    pub display: Vec<DisplayRule>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            battery_backend: BatteryBackendKind::default(),
+            udev_subsystems: None,
             battery: vec![BatteryRule::default()],
             power_supply: vec![PowerStatusRule::default()],
             cpu: vec![CPURule::default()],
             memory: vec![MemoryRule::default()],
             storage: vec![StorageRule::default()],
             device: vec![DeviceRule::default()],
+            network: vec![NetworkRule::default()],
+            wifi: vec![WifiRule::default()],
+            link: vec![LinkRule::default()],
+            bandwidth: vec![BandwidthRule::default()],
+            probe: vec![ProbeRule::default()],
+            temperature: vec![TemperatureRule::default()],
+            fan: vec![FanRule::default()],
+            process: vec![ProcessRule::default()],
+            oom: vec![OomRule::default()],
+            kernel_log: vec![KernelLogRule::default()],
+            journal: vec![JournalRule::default()],
+            systemd_unit: vec![SystemdUnitRule::default()],
+            session: vec![SessionRule::default()],
+            display: vec![DisplayRule::default()],
         }
     }
 }
 
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BatteryBackendKind {
+    /// UPower when it is reachable on the system bus, sysfs otherwise
+    #[default]
+    Auto,
+    UPower,
+    Sysfs,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 #[serde(default)]
 pub struct BatteryRule {
@@ -62,7 +126,14 @@ impl Default for BatteryRule {
 #[derive(Debug, Deserialize, Clone)]
 #[serde(default)]
 pub struct CPURule {
+    pub event: String,
     pub level: f32,
+    /// Fraction of `cpuinfo_max_freq` the average frequency must stay below
+    pub fraction: f32,
+    /// Seconds the frequency must stay below `fraction` before notifying
+    pub duration: u64,
+    /// Number of top consumers exposed as `top*` fields
+    pub top: usize,
     #[serde(flatten)]
     pub message: Message,
 }
@@ -70,7 +141,11 @@ pub struct CPURule {
 impl Default for CPURule {
     fn default() -> Self {
         Self {
+            event: "usage".to_string(),
             level: 90.0,
+            fraction: 0.5,
+            duration: 60,
+            top: 3,
             message: Message {
                 urgency: "normal".to_string(),
                 appname: "CPU".to_string(),
@@ -84,6 +159,8 @@ impl Default for CPURule {
 #[serde(default)]
 pub struct MemoryRule {
     pub level: f32,
+    /// Number of top consumers exposed as `top*` fields
+    pub top: usize,
     #[serde(flatten)]
     pub message: Message,
 }
@@ -92,6 +169,7 @@ impl Default for MemoryRule {
     fn default() -> Self {
         Self {
             level: 90.0,
+            top: 3,
             message: Message {
                 urgency: "normal".to_string(),
                 appname: "Memory".to_string(),
@@ -105,6 +183,10 @@ impl Default for MemoryRule {
 #[serde(default)]
 pub struct StorageRule {
     pub level: f32,
+    pub mount: Option<Matcher>,
+    pub name: Option<Matcher>,
+    pub fs: Option<Matcher>,
+    pub kind: Option<Matcher>,
     #[serde(flatten)]
     pub message: Message,
 }
@@ -113,6 +195,10 @@ impl Default for StorageRule {
     fn default() -> Self {
         Self {
             level: 95.0,
+            mount: None,
+            name: None,
+            fs: None,
+            kind: None,
             message: Message {
                 urgency: "normal".to_string(),
                 appname: "Storage".to_string(),
@@ -127,11 +213,26 @@ impl Default for StorageRule {
 pub struct DeviceRule {
     pub action: String,
     pub initialized: Option<bool>,
-    pub subsystem: Option<String>,
-    pub sysname: Option<String>,
+    pub subsystem: Option<Matcher>,
+    pub sysname: Option<Matcher>,
     pub sysnum: Option<i32>,
-    pub devtype: Option<String>,
-    pub driver: Option<String>,
+    pub devtype: Option<Matcher>,
+    pub driver: Option<Matcher>,
+    /// USB vendor id, e.g. `"0781"`
+    #[serde(deserialize_with = "matcher::ignore_case")]
+    pub vendor_id: Option<Matcher>,
+    /// USB product id, e.g. `"5581"`
+    #[serde(deserialize_with = "matcher::ignore_case")]
+    pub product_id: Option<Matcher>,
+    pub serial: Option<Matcher>,
+    /// udev properties that must all be present with these values, e.g. `{ ID_BUS = "usb" }`
+    pub properties: HashMap<String, Matcher>,
+    /// sysfs attributes that must all be present with these values, e.g. `{ removable = "1" }`
+    pub attributes: HashMap<String, Matcher>,
+    /// udev tags that must all be set on the device, e.g. `["uaccess"]`
+    pub tags: Vec<String>,
+    /// Also match devices already present at startup as `add` events
+    pub on_startup: bool,
     #[serde(flatten)]
     pub message: Message,
 }
@@ -146,6 +247,13 @@ impl Default for DeviceRule {
             sysnum: None,
             devtype: None,
             driver: None,
+            vendor_id: None,
+            product_id: None,
+            serial: None,
+            properties: HashMap::new(),
+            attributes: HashMap::new(),
+            tags: Vec::new(),
+            on_startup: false,
             message: Message {
                 urgency: "low".to_string(),
                 appname: "Device".to_string(),
@@ -158,10 +266,15 @@ impl Default for DeviceRule {
 #[derive(Debug, Deserialize, Clone)]
 #[serde(default)]
 pub struct PowerStatusRule {
-    pub name: Option<String>,
+    pub name: Option<Matcher>,
     #[serde(rename = "type")]
-    pub supply_type: Option<String>,
-    pub online: Option<String>,
+    pub supply_type: Option<Matcher>,
+    pub online: Option<Matcher>,
+    pub properties: HashMap<String, Matcher>,
+    /// Fire only when the rule starts matching a supply, not on every change event
+    pub edge: bool,
+    /// Notify about the state of supplies at startup when the rule matches
+    pub on_startup: bool,
     #[serde(flatten)]
     pub message: Message,
 }
@@ -172,6 +285,9 @@ impl Default for PowerStatusRule {
             name: None,
             supply_type: None,
             online: None,
+            properties: HashMap::new(),
+            edge: true,
+            on_startup: false,
             message: Message {
                 urgency: "low".to_string(),
                 appname: "Power supply".to_string(),
@@ -181,6 +297,414 @@ impl Default for PowerStatusRule {
     }
 }
 
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct NetworkRule {
+    pub state: Option<Matcher>,
+    pub connectivity: Option<Matcher>,
+    pub id: Option<Matcher>,
+    #[serde(rename = "type")]
+    pub connection_type: Option<Matcher>,
+    pub device: Option<Matcher>,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+impl Default for NetworkRule {
+    fn default() -> Self {
+        Self {
+            state: None,
+            connectivity: None,
+            id: None,
+            connection_type: None,
+            device: None,
+            message: Message {
+                urgency: "low".to_string(),
+                appname: "Network".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct WifiRule {
+    pub event: String,
+    pub level: f32,
+    pub device: Option<Matcher>,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+impl Default for WifiRule {
+    fn default() -> Self {
+        Self {
+            event: "signal".to_string(),
+            level: 30.0,
+            device: None,
+            message: Message {
+                urgency: "low".to_string(),
+                appname: "Wi-Fi".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct LinkRule {
+    pub event: String,
+    pub interface: Option<Matcher>,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+impl Default for LinkRule {
+    fn default() -> Self {
+        Self {
+            event: "down".to_string(),
+            interface: None,
+            message: Message {
+                urgency: "normal".to_string(),
+                appname: "Network".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct BandwidthRule {
+    pub interface: Option<Matcher>,
+    pub direction: String,
+    /// Throughput threshold in bytes per second
+    pub rate: Option<u64>,
+    /// Seconds the rate must be exceeded before notifying
+    pub duration: u64,
+    /// Traffic threshold in bytes per period
+    pub quota: Option<u64>,
+    pub period: String,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+impl Default for BandwidthRule {
+    fn default() -> Self {
+        Self {
+            interface: None,
+            direction: "total".to_string(),
+            rate: None,
+            duration: 0,
+            quota: None,
+            period: "daily".to_string(),
+            message: Message {
+                urgency: "normal".to_string(),
+                appname: "Bandwidth".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ProbeRule {
+    pub kind: String,
+    /// Host for `icmp`, `host:port` for `tcp` and URL for `http` probes
+    pub target: String,
+    pub event: String,
+    /// Seconds between probes
+    pub interval: u64,
+    /// Seconds to wait for a reply
+    pub probe_timeout: u64,
+    /// Latency threshold in milliseconds for the `latency` event
+    pub latency: u64,
+    pub expect_status: Option<u16>,
+    pub expect_body: Option<String>,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+impl Default for ProbeRule {
+    fn default() -> Self {
+        Self {
+            kind: "tcp".to_string(),
+            target: String::new(),
+            event: "failure".to_string(),
+            interval: 60,
+            probe_timeout: 5,
+            latency: 500,
+            expect_status: None,
+            expect_body: None,
+            message: Message {
+                urgency: "normal".to_string(),
+                appname: "Probe".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct TemperatureRule {
+    pub chip: Option<Matcher>,
+    pub sensor: Option<Matcher>,
+    /// Threshold in degrees Celsius
+    pub level: Option<f32>,
+    /// Use the sensor's own `crit` or `max` value as the threshold
+    pub limit: Option<String>,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+impl Default for TemperatureRule {
+    fn default() -> Self {
+        Self {
+            chip: None,
+            sensor: None,
+            level: None,
+            limit: None,
+            message: Message {
+                urgency: "critical".to_string(),
+                appname: "Temperature".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct FanRule {
+    pub chip: Option<Matcher>,
+    pub fan: Option<Matcher>,
+    /// Notify when the fan spins slower than this, in RPM
+    pub min: Option<u32>,
+    /// Notify when the fan spins faster than this, in RPM
+    pub max: Option<u32>,
+    /// Only notify while the hottest sensor of the chip reaches this, in °C
+    pub temp: Option<f32>,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+impl Default for FanRule {
+    fn default() -> Self {
+        Self {
+            chip: None,
+            fan: None,
+            min: None,
+            max: None,
+            temp: None,
+            message: Message {
+                urgency: "critical".to_string(),
+                appname: "Fan".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ProcessRule {
+    pub event: String,
+    pub name: Option<Matcher>,
+    /// Regex matched against the space-joined command line
+    pub cmdline: Option<String>,
+    pub user: Option<Matcher>,
+    pub exe: Option<Matcher>,
+    /// CPU usage threshold for the `cpu` event, in percent of one core
+    pub level: f32,
+    /// Resident memory threshold for the `rss` event, in bytes
+    pub rss: Option<u64>,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+impl Default for ProcessRule {
+    fn default() -> Self {
+        Self {
+            event: "not_running".to_string(),
+            name: None,
+            cmdline: None,
+            user: None,
+            exe: None,
+            level: 90.0,
+            rss: None,
+            message: Message {
+                urgency: "normal".to_string(),
+                appname: "Process".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct OomRule {
+    /// `kernel` for the kernel OOM killer, `oomd` for systemd-oomd, both when unset
+    pub source: Option<String>,
+    pub name: Option<Matcher>,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+impl Default for OomRule {
+    fn default() -> Self {
+        Self {
+            source: None,
+            name: None,
+            message: Message {
+                urgency: "critical".to_string(),
+                appname: "OOM".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct KernelLogRule {
+    /// Regex matched against the message, capture groups become fields
+    pub pattern: String,
+    /// Least severe priority to match, e.g. `err` also matches `crit`
+    pub priority: Option<String>,
+    pub facility: Option<String>,
+    /// Minimum seconds between two notifications of this rule
+    pub interval: u64,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+impl Default for KernelLogRule {
+    fn default() -> Self {
+        Self {
+            pattern: String::new(),
+            priority: None,
+            facility: None,
+            interval: 60,
+            message: Message {
+                urgency: "normal".to_string(),
+                appname: "Kernel".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct JournalRule {
+    /// Unit pattern, matches system and user units
+    pub unit: Option<Matcher>,
+    /// Syslog identifier pattern, e.g. `"sshd"`
+    pub identifier: Option<Matcher>,
+    /// Least severe priority to match, e.g. `err` also matches `crit`
+    pub priority: Option<String>,
+    /// Regex matched against the message, capture groups become fields
+    pub pattern: Option<String>,
+    /// Minimum seconds between two notifications of this rule
+    pub interval: u64,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+impl Default for JournalRule {
+    fn default() -> Self {
+        Self {
+            unit: None,
+            identifier: None,
+            priority: None,
+            pattern: None,
+            interval: 60,
+            message: Message {
+                urgency: "normal".to_string(),
+                appname: "Journal".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SystemdUnitRule {
+    /// `system` or `user` manager, both when unset
+    pub scope: Option<String>,
+    pub unit: Option<Matcher>,
+    /// Failure result, e.g. `"exit-code"`, `"oom-kill"`, `"timeout"`
+    pub result: Option<Matcher>,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+impl Default for SystemdUnitRule {
+    fn default() -> Self {
+        Self {
+            scope: None,
+            unit: None,
+            result: None,
+            message: Message {
+                urgency: "critical".to_string(),
+                appname: "systemd".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SessionRule {
+    pub event: String,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+impl Default for SessionRule {
+    fn default() -> Self {
+        Self {
+            event: "resume".to_string(),
+            message: Message {
+                urgency: "low".to_string(),
+                appname: "Session".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct DisplayRule {
+    pub event: String,
+    /// Connector pattern, e.g. `"HDMI-A-*"`, `"eDP-1"`
+    pub connector: Option<Matcher>,
+    #[serde(flatten)]
+    pub message: Message,
+}
+
+impl Default for DisplayRule {
+    fn default() -> Self {
+        Self {
+            event: "connect".to_string(),
+            connector: None,
+            message: Message {
+                urgency: "low".to_string(),
+                appname: "Display".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
 fn get_config_path() -> Result<PathBuf> {
     let config_dir = get_config_dir()?;
     Ok(config_dir.join(CONFIG_FILE_NAME))
@@ -246,14 +770,26 @@ fn validate_config(config: &Config) -> Result<()> {
         }
     }
 
-    // Validate CPU levels
+    // Validate CPU rules
     for (i, rule) in config.cpu.iter().enumerate() {
+        if !cpu::CPU_EVENTS.contains(&rule.event.as_str()) {
+            return Err(anyhow::anyhow!(
+                "CPU rule {}: unknown event {:?}, expected one of {:?}",
+                i, rule.event, cpu::CPU_EVENTS
+            ));
+        }
         if !(0.0..=100.0).contains(&rule.level) {
             return Err(anyhow::anyhow!(
                 "CPU rule {}: level must be between 0 and 100, got {}",
                 i, rule.level
             ));
         }
+        if !(0.0..=1.0).contains(&rule.fraction) {
+            return Err(anyhow::anyhow!(
+                "CPU rule {}: fraction must be between 0 and 1, got {}",
+                i, rule.fraction
+            ));
+        }
     }
 
     // Validate memory levels
@@ -276,5 +812,246 @@ fn validate_config(config: &Config) -> Result<()> {
         }
     }
 
+    // Validate network state names
+    for (i, rule) in config.network.iter().enumerate() {
+        for state in rule.state.iter().flat_map(Matcher::exact_values) {
+            if !network::STATE_NAMES.contains(&state) {
+                return Err(anyhow::anyhow!(
+                    "Network rule {}: unknown state {:?}, expected one of {:?}",
+                    i, state, network::STATE_NAMES
+                ));
+            }
+        }
+        for connectivity in rule.connectivity.iter().flat_map(Matcher::exact_values) {
+            if !network::CONNECTIVITY_NAMES.contains(&connectivity) {
+                return Err(anyhow::anyhow!(
+                    "Network rule {}: unknown connectivity {:?}, expected one of {:?}",
+                    i, connectivity, network::CONNECTIVITY_NAMES
+                ));
+            }
+        }
+    }
+
+    // Validate wifi rules
+    for (i, rule) in config.wifi.iter().enumerate() {
+        if !network::WIFI_EVENTS.contains(&rule.event.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Wifi rule {}: unknown event {:?}, expected one of {:?}",
+                i, rule.event, network::WIFI_EVENTS
+            ));
+        }
+        if !(0.0..=100.0).contains(&rule.level) {
+            return Err(anyhow::anyhow!(
+                "Wifi rule {}: level must be between 0 and 100, got {}",
+                i, rule.level
+            ));
+        }
+    }
+
+    // Validate link events
+    for (i, rule) in config.link.iter().enumerate() {
+        if !netlink::LINK_EVENTS.contains(&rule.event.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Link rule {}: unknown event {:?}, expected one of {:?}",
+                i, rule.event, netlink::LINK_EVENTS
+            ));
+        }
+    }
+
+    // Validate bandwidth rules
+    for (i, rule) in config.bandwidth.iter().enumerate() {
+        if rule.rate.is_some() == rule.quota.is_some() {
+            return Err(anyhow::anyhow!(
+                "Bandwidth rule {}: exactly one of rate or quota must be set",
+                i
+            ));
+        }
+        if !bandwidth::DIRECTIONS.contains(&rule.direction.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Bandwidth rule {}: unknown direction {:?}, expected one of {:?}",
+                i, rule.direction, bandwidth::DIRECTIONS
+            ));
+        }
+        if !bandwidth::PERIODS.contains(&rule.period.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Bandwidth rule {}: unknown period {:?}, expected one of {:?}",
+                i, rule.period, bandwidth::PERIODS
+            ));
+        }
+    }
+
+    // Validate probe rules
+    for (i, rule) in config.probe.iter().enumerate() {
+        if rule.target.is_empty() {
+            return Err(anyhow::anyhow!("Probe rule {}: target must be set", i));
+        }
+        if !probe::PROBE_KINDS.contains(&rule.kind.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Probe rule {}: unknown kind {:?}, expected one of {:?}",
+                i, rule.kind, probe::PROBE_KINDS
+            ));
+        }
+        if !probe::PROBE_EVENTS.contains(&rule.event.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Probe rule {}: unknown event {:?}, expected one of {:?}",
+                i, rule.event, probe::PROBE_EVENTS
+            ));
+        }
+        if rule.interval == 0 {
+            return Err(anyhow::anyhow!("Probe rule {}: interval must be greater than 0", i));
+        }
+        if rule.probe_timeout == 0 {
+            return Err(anyhow::anyhow!("Probe rule {}: probe_timeout must be greater than 0", i));
+        }
+        if let Some(body) = &rule.expect_body {
+            regex::Regex::new(body)
+                .with_context(|| format!("Probe rule {}: invalid expect_body regex", i))?;
+        }
+    }
+
+    // Validate temperature rules
+    for (i, rule) in config.temperature.iter().enumerate() {
+        if rule.level.is_none() && rule.limit.is_none() {
+            return Err(anyhow::anyhow!(
+                "Temperature rule {}: level or limit must be set",
+                i
+            ));
+        }
+        if let Some(limit) = &rule.limit {
+            if !temperature::LIMITS.contains(&limit.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "Temperature rule {}: unknown limit {:?}, expected one of {:?}",
+                    i, limit, temperature::LIMITS
+                ));
+            }
+        }
+    }
+
+    // Validate fan rules
+    for (i, rule) in config.fan.iter().enumerate() {
+        if let (Some(min), Some(max)) = (rule.min, rule.max) {
+            if min > max {
+                return Err(anyhow::anyhow!(
+                    "Fan rule {}: min ({}) must not be greater than max ({})",
+                    i, min, max
+                ));
+            }
+        }
+    }
+
+    // Validate process rules
+    for (i, rule) in config.process.iter().enumerate() {
+        if !process::PROCESS_EVENTS.contains(&rule.event.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Process rule {}: unknown event {:?}, expected one of {:?}",
+                i, rule.event, process::PROCESS_EVENTS
+            ));
+        }
+        if rule.name.is_none() && rule.cmdline.is_none() && rule.user.is_none() && rule.exe.is_none() {
+            return Err(anyhow::anyhow!(
+                "Process rule {}: at least one of name, cmdline, user or exe must be set",
+                i
+            ));
+        }
+        if rule.event == "rss" && rule.rss.is_none() {
+            return Err(anyhow::anyhow!("Process rule {}: rss must be set for the rss event", i));
+        }
+        if let Some(cmdline) = &rule.cmdline {
+            regex::Regex::new(cmdline)
+                .with_context(|| format!("Process rule {}: invalid cmdline regex", i))?;
+        }
+    }
+
+    // Validate OOM rules
+    for (i, rule) in config.oom.iter().enumerate() {
+        if let Some(source) = &rule.source {
+            if !oom::OOM_SOURCES.contains(&source.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "OOM rule {}: unknown source {:?}, expected one of {:?}",
+                    i, source, oom::OOM_SOURCES
+                ));
+            }
+        }
+    }
+
+    // Validate kernel log rules
+    for (i, rule) in config.kernel_log.iter().enumerate() {
+        if rule.pattern.is_empty() {
+            return Err(anyhow::anyhow!("Kernel log rule {}: pattern must be set", i));
+        }
+        regex::Regex::new(&rule.pattern)
+            .with_context(|| format!("Kernel log rule {}: invalid pattern regex", i))?;
+        if let Some(priority) = &rule.priority {
+            if !kmsg::PRIORITIES.contains(&priority.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "Kernel log rule {}: unknown priority {:?}, expected one of {:?}",
+                    i, priority, kmsg::PRIORITIES
+                ));
+            }
+        }
+        if let Some(facility) = &rule.facility {
+            if !kmsg::FACILITIES.contains(&facility.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "Kernel log rule {}: unknown facility {:?}, expected one of {:?}",
+                    i, facility, kmsg::FACILITIES
+                ));
+            }
+        }
+    }
+
+    // Validate journal rules
+    for (i, rule) in config.journal.iter().enumerate() {
+        if rule.unit.is_none() && rule.identifier.is_none() && rule.priority.is_none() && rule.pattern.is_none() {
+            return Err(anyhow::anyhow!(
+                "Journal rule {}: at least one of unit, identifier, priority or pattern must be set",
+                i
+            ));
+        }
+        if let Some(pattern) = &rule.pattern {
+            regex::Regex::new(pattern)
+                .with_context(|| format!("Journal rule {}: invalid pattern regex", i))?;
+        }
+        if let Some(priority) = &rule.priority {
+            if !kmsg::PRIORITIES.contains(&priority.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "Journal rule {}: unknown priority {:?}, expected one of {:?}",
+                    i, priority, kmsg::PRIORITIES
+                ));
+            }
+        }
+    }
+
+    // Validate systemd unit rules
+    for (i, rule) in config.systemd_unit.iter().enumerate() {
+        if let Some(scope) = &rule.scope {
+            if !systemd::SCOPES.contains(&scope.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "Systemd unit rule {}: unknown scope {:?}, expected one of {:?}",
+                    i, scope, systemd::SCOPES
+                ));
+            }
+        }
+    }
+
+    // Validate session events
+    for (i, rule) in config.session.iter().enumerate() {
+        if !session::SESSION_EVENTS.contains(&rule.event.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Session rule {}: unknown event {:?}, expected one of {:?}",
+                i, rule.event, session::SESSION_EVENTS
+            ));
+        }
+    }
+
+    // Validate display events
+    for (i, rule) in config.display.iter().enumerate() {
+        if !display::DISPLAY_EVENTS.contains(&rule.event.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Display rule {}: unknown event {:?}, expected one of {:?}",
+                i, rule.event, display::DISPLAY_EVENTS
+            ));
+        }
+    }
+
     Ok(())
 }
@@ -1,15 +1,93 @@
 use anyhow::Result;
-use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}, time::Duration};
+use std::{collections::{HashMap, HashSet}, fs, path::{Path, PathBuf}, sync::{Arc, Mutex}, time::{Duration, Instant}};
 use sysinfo::System;
-use tokio::{task, time::sleep};
+use tokio::task;
 
-use crate::{config::CPURule, utils::execute_command};
+use crate::{
+    config::CPURule,
+    process,
+    session::{interruptible_sleep, ResumeWatch},
+    utils::execute_command,
+};
+
+const CPU_DIR: &str = "/sys/devices/system/cpu";
+
+pub const CPU_EVENTS: &[&str] = &["usage", "throttle", "frequency"];
+
+fn read_string(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+fn cpu_dirs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .filter(|entry| {
+            entry.file_name()
+                .to_string_lossy()
+                .strip_prefix("cpu")
+                .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// Sums the throttle counters of every core and package. Each cpu reports
+/// the counters of its core and package, so SMT siblings and the cpus of a
+/// package are counted once.
+fn read_throttle_count(cpus: &[PathBuf]) -> u64 {
+    let mut cores = HashSet::new();
+    let mut packages = HashSet::new();
+    let mut total = 0;
+
+    for cpu in cpus {
+        // without topology every cpu stands for its own core and package
+        let topology = |name: &str| read_string(&cpu.join("topology").join(name))
+            .unwrap_or_else(|| cpu.display().to_string());
+        let counter = |name: &str| read_string(&cpu.join("thermal_throttle").join(name))
+            .and_then(|count| count.parse::<u64>().ok())
+            .unwrap_or(0);
+
+        let package = topology("physical_package_id");
+        if cores.insert((package.clone(), topology("core_id"))) {
+            total += counter("core_throttle_count");
+        }
+        if packages.insert(package) {
+            total += counter("package_throttle_count");
+        }
+    }
+
+    total
+}
+
+/// Highest `cpuinfo_max_freq` of all cpus, in MHz.
+fn read_max_freq(cpus: &[PathBuf]) -> Option<u64> {
+    cpus.iter()
+        .filter_map(|cpu| read_string(&cpu.join("cpufreq/cpuinfo_max_freq"))?.parse::<u64>().ok())
+        .max()
+        .map(|khz| khz / 1000)
+}
 
 pub async fn monitor_cpu(rules: Vec<CPURule>, sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    let cpus = cpu_dirs(Path::new(CPU_DIR));
+    let hardware_max_freq = read_max_freq(&cpus);
+    let mut previous_throttle_count = read_throttle_count(&cpus);
+    let mut below_since: HashMap<usize, Instant> = HashMap::new();
+    let mut resume = ResumeWatch::new();
+
+    // kept across iterations, cpu usage is computed between two refreshes
+    let mut sys = System::new();
+    let top_count = rules.iter().map(|rule| process::top_count(rule.top, &rule.message)).max().unwrap_or(0);
+
     loop {
-        let mut sys = System::new();
         sys.refresh_cpu_all();
         sys.refresh_cpu_usage();
+        if top_count > 0 {
+            process::refresh_usage(&mut sys);
+        }
 
         let used_percent = sys.global_cpu_usage();
         let left_percent = 100.0 - used_percent;
@@ -18,43 +96,136 @@ pub async fn monitor_cpu(rules: Vec<CPURule>, sent: Arc<Mutex<HashSet<String>>>)
         let max_freq = freqs.clone().max().unwrap();
         let avg_freq = freqs.sum::<u64>() / sys.cpus().len() as u64;
 
-        for rule in &rules {
-            let should_notify = {
-                let key = format!("cpu-{}", rule.level);
-                let mut sent_guard = sent.lock().unwrap();
+        let throttle_count = read_throttle_count(&cpus);
+        // cpus are taken offline and back during suspend, which resets their
+        // counters and the frequency has to stay low for a whole duration awake
+        if resume.resumed() {
+            previous_throttle_count = throttle_count;
+            below_since.clear();
+        }
+        // counters are reset when cpus go offline and come back
+        let throttle_delta = throttle_count.saturating_sub(previous_throttle_count);
+        previous_throttle_count = throttle_count;
+
+        let governor = cpus.first()
+            .and_then(|cpu| read_string(&cpu.join("cpufreq/scaling_governor")))
+            .unwrap_or_default();
+        let epp = cpus.first()
+            .and_then(|cpu| read_string(&cpu.join("cpufreq/energy_performance_preference")))
+            .unwrap_or_default();
 
-                if used_percent >= rule.level {
-                    if sent_guard.contains(&key) {
+        let now = Instant::now();
+
+        for (index, rule) in rules.iter().enumerate() {
+            let should_notify = match rule.event.as_str() {
+                "usage" => {
+                    let key = format!("cpu-{}", rule.level);
+                    let mut sent_guard = sent.lock().unwrap();
+
+                    if used_percent >= rule.level {
+                        if sent_guard.contains(&key) {
+                            false
+                        } else {
+                            sent_guard.insert(key);
+                            true
+                        }
+                    } else {
+                        sent_guard.remove(&key);
                         false
+                    }
+                }
+                "throttle" => {
+                    let key = format!("cpu-throttle-{}", index);
+                    let mut sent_guard = sent.lock().unwrap();
+
+                    if throttle_delta > 0 {
+                        sent_guard.insert(key)
+                    } else {
+                        sent_guard.remove(&key);
+                        false
+                    }
+                }
+                "frequency" => {
+                    let key = format!("cpu-frequency-{}", index);
+                    let mut sent_guard = sent.lock().unwrap();
+
+                    let below = hardware_max_freq
+                        .is_some_and(|max| (avg_freq as f32) < max as f32 * rule.fraction);
+
+                    if below {
+                        let since = *below_since.entry(index).or_insert(now);
+                        now.duration_since(since) >= Duration::from_secs(rule.duration)
+                            && sent_guard.insert(key)
                     } else {
-                        sent_guard.insert(key);
-                        true
+                        below_since.remove(&index);
+                        sent_guard.remove(&key);
+                        false
                     }
-                } else {
-                    sent_guard.remove(&key);
-                    false
                 }
+                _ => false,
             };
 
             if should_notify {
                 let mut fields = HashMap::new();
                 fields.insert("level",             rule.level.to_string());
+                fields.insert("event",             rule.event.clone());
                 fields.insert("max_freq",          max_freq.to_string());
                 fields.insert("avg_freq",          avg_freq.to_string());
+                fields.insert("hw_max_freq",       hardware_max_freq.map(|f| f.to_string()).unwrap_or_default());
+                fields.insert("throttle_count",    throttle_delta.to_string());
+                fields.insert("governor",          governor.clone());
+                fields.insert("epp",               epp.clone());
                 fields.insert("used_percent_full", used_percent.to_string());
                 fields.insert("used_percent",      (used_percent as u32).to_string());
                 fields.insert("left_percent_full", left_percent.to_string());
                 fields.insert("left_percent",      (left_percent as u32).to_string());
 
+                let top = process::top_fields(&sys, process::top_count(rule.top, &rule.message), true);
+
                 let rule_clone = rule.clone();
                 let _ = execute_command(rule_clone.message.exec.as_ref());
                 task::spawn_blocking(move || {
-                    let _ = rule_clone.message.notify(&fields);
+                    let _ = rule_clone.message.notify(
+                        &fields
+                            .into_iter()
+                            .chain(top.iter().map(|(k, v)| (k.as_str(), v.clone())))
+                            .collect(),
+                    );
                 })
                 .await?;
             }
         }
 
-        sleep(Duration::from_secs(10)).await;
+        interruptible_sleep(Duration::from_secs(10)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu(dir: &Path, n: u32, package: u32, core: u32, core_count: u64, package_count: u64) {
+        let path = dir.join(format!("cpu{}", n));
+        fs::create_dir_all(path.join("topology")).unwrap();
+        fs::create_dir_all(path.join("thermal_throttle")).unwrap();
+        fs::write(path.join("topology/physical_package_id"), format!("{}\n", package)).unwrap();
+        fs::write(path.join("topology/core_id"), format!("{}\n", core)).unwrap();
+        fs::write(path.join("thermal_throttle/core_throttle_count"), format!("{}\n", core_count)).unwrap();
+        fs::write(path.join("thermal_throttle/package_throttle_count"), format!("{}\n", package_count)).unwrap();
+    }
+
+    #[test]
+    fn throttle_counters_are_counted_once() {
+        let dir = tempfile::tempdir().unwrap();
+        // two packages with two cores of two SMT siblings each
+        for n in 0..8 {
+            let (package, core) = (n / 4, n / 2 % 2);
+            cpu(dir.path(), n, package, core, 10 * (package * 2 + core + 1) as u64, 1000 * (package + 1) as u64);
+        }
+        fs::create_dir(dir.path().join("cpufreq")).unwrap();
+
+        let cpus = cpu_dirs(dir.path());
+        assert_eq!(cpus.len(), 8);
+        assert_eq!(read_throttle_count(&cpus), 10 + 20 + 30 + 40 + 1000 + 2000);
     }
 }
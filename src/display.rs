@@ -0,0 +1,245 @@
+use anyhow::Result;
+use std::{collections::HashMap, fs, path::Path};
+use tokio::task;
+
+use crate::{config::DisplayRule, utils::execute_command};
+
+const DRM_DIR: &str = "/sys/class/drm";
+const EDID_HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+
+pub const DISPLAY_EVENTS: &[&str] = &["connect", "disconnect"];
+
+/// The parts of a monitor's EDID base block worth showing to a user.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Edid {
+    pub manufacturer: String,
+    pub product_code: String,
+    pub model: String,
+    pub serial: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Edid {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 128 || data[..8] != EDID_HEADER {
+            return None;
+        }
+
+        // three 5-bit letters, 1 is 'A'
+        let id = u16::from_be_bytes([data[8], data[9]]);
+        let manufacturer = [10, 5, 0]
+            .iter()
+            .map(|shift| (b'A' - 1 + ((id >> shift) & 0x1f) as u8) as char)
+            .collect();
+        let product_code = format!("{:04x}", u16::from_le_bytes([data[10], data[11]]));
+        let serial_number = u32::from_le_bytes([data[12], data[13], data[14], data[15]]);
+
+        let mut edid = Self {
+            manufacturer,
+            product_code,
+            serial: if serial_number != 0 { serial_number.to_string() } else { String::new() },
+            ..Default::default()
+        };
+
+        for (i, descriptor) in data[54..126].chunks(18).enumerate() {
+            let pixel_clock = u16::from_le_bytes([descriptor[0], descriptor[1]]);
+
+            // the first detailed timing is the preferred, native mode
+            if pixel_clock != 0 {
+                if i == 0 {
+                    edid.width = descriptor[2] as u32 | ((descriptor[4] as u32 & 0xf0) << 4);
+                    edid.height = descriptor[5] as u32 | ((descriptor[7] as u32 & 0xf0) << 4);
+                }
+                continue;
+            }
+
+            let text = String::from_utf8_lossy(&descriptor[5..18])
+                .split('\n')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+
+            match descriptor[3] {
+                0xfc => edid.model = text,
+                0xff => edid.serial = text,
+                _ => {}
+            }
+        }
+
+        if edid.model.is_empty() {
+            edid.model = edid.product_code.clone();
+        }
+
+        Some(edid)
+    }
+
+    pub fn resolution(&self) -> String {
+        if self.width == 0 || self.height == 0 {
+            return String::new();
+        }
+        format!("{}x{}", self.width, self.height)
+    }
+}
+
+/// Connected connectors by sysfs name with their decoded EDID. The name
+/// includes the card, e.g. `card1-DP-1`, since connector names repeat across
+/// the GPUs of hybrid graphics laptops.
+pub type Displays = HashMap<String, Option<Edid>>;
+
+/// Splits a sysfs connector name like `card0-HDMI-A-1` into card and connector.
+fn split_connector(name: &str) -> Option<(&str, &str)> {
+    let (card, connector) = name.split_once('-')?;
+    card.strip_prefix("card")?.parse::<u32>().ok()?;
+    Some((card, connector))
+}
+
+pub fn scan_connectors() -> Displays {
+    let Ok(entries) = fs::read_dir(DRM_DIR) else {
+        return Displays::new();
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            split_connector(&name)?;
+            let path = entry.path();
+
+            let status = fs::read_to_string(path.join("status")).ok()?;
+            if status.trim() != "connected" {
+                return None;
+            }
+
+            Some((name, read_edid(&path)))
+        })
+        .collect()
+}
+
+fn read_edid(connector: &Path) -> Option<Edid> {
+    Edid::parse(&fs::read(connector.join("edid")).ok()?)
+}
+
+async fn notify_display(rules: &[DisplayRule], event: &str, name: &str, edid: Option<&Edid>) -> Result<()> {
+    let (card, connector) = split_connector(name).unwrap_or(("", name));
+
+    for rule in rules.iter()
+        .filter(|rule| rule.event == event)
+        .filter(|rule| rule.connector.as_ref().is_none_or(|matcher| matcher.matches(connector)))
+    {
+        let edid = edid.cloned().unwrap_or_default();
+
+        let mut fields = HashMap::new();
+        fields.insert("event",        event.to_string());
+        fields.insert("card",         card.to_string());
+        fields.insert("connector",    connector.to_string());
+        fields.insert("manufacturer", edid.manufacturer.clone());
+        fields.insert("model",        edid.model.clone());
+        fields.insert("product_code", edid.product_code.clone());
+        fields.insert("serial",       edid.serial.clone());
+        fields.insert("resolution",   edid.resolution());
+
+        let rule_clone = rule.clone();
+        let _ = execute_command(rule_clone.message.exec.as_ref());
+        task::spawn_blocking(move || {
+            let _ = rule_clone.message.notify(&fields);
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Rescans connectors after a drm change event, the kernel reports hotplug
+/// on the card rather than on the connector that changed.
+pub async fn handle_drm_change(rules: &[DisplayRule], displays: &mut Displays) -> Result<()> {
+    // the new state is kept even when a notification fails, so that the
+    // next event doesn't report the same change again
+    let previous = std::mem::replace(displays, scan_connectors());
+    let current = &*displays;
+
+    for (name, edid) in current {
+        match previous.get(name) {
+            None => notify_display(rules, "connect", name, edid.as_ref()).await?,
+            // a different monitor plugged in between two events counts as a new connection
+            Some(Some(previous)) if edid.as_ref().is_some_and(|edid| edid != previous) => {
+                notify_display(rules, "disconnect", name, Some(previous)).await?;
+                notify_display(rules, "connect", name, edid.as_ref()).await?;
+            }
+            Some(_) => {}
+        }
+    }
+    for (name, edid) in &previous {
+        if !current.contains_key(name) {
+            notify_display(rules, "disconnect", name, edid.as_ref()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Base block of a Dell U2415: 1920x1200 native mode, serial and name in
+    /// display descriptors.
+    const DELL_U2415: [u8; 128] = [
+        0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x10, 0xac, 0xc4, 0xa0, 0x4c, 0x4b, 0x31, 0x30,
+        0x0c, 0x19, 0x01, 0x04, 0xa5, 0x34, 0x20, 0x78, 0x3a, 0xee, 0x95, 0xa3, 0x54, 0x4c, 0x99, 0x26,
+        0x0f, 0x50, 0x54, 0xa5, 0x4b, 0x00, 0x71, 0x4f, 0x81, 0x80, 0xa9, 0x40, 0xd1, 0xc0, 0xd1, 0x00,
+        0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x28, 0x3c, 0x80, 0xa0, 0x70, 0xb0, 0x23, 0x40, 0x30, 0x20,
+        0x36, 0x00, 0x06, 0x44, 0x21, 0x00, 0x00, 0x1a, 0x00, 0x00, 0x00, 0xff, 0x00, 0x37, 0x4d, 0x54,
+        0x30, 0x31, 0x38, 0x34, 0x52, 0x31, 0x42, 0x50, 0x4c, 0x0a, 0x00, 0x00, 0x00, 0xfc, 0x00, 0x44,
+        0x45, 0x4c, 0x4c, 0x20, 0x55, 0x32, 0x34, 0x31, 0x35, 0x0a, 0x20, 0x20, 0x00, 0x00, 0x00, 0xfd,
+        0x00, 0x38, 0x4c, 0x1e, 0x51, 0x11, 0x00, 0x0a, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00, 0x4b,
+    ];
+
+    #[test]
+    fn parse_edid() {
+        assert_eq!(DELL_U2415.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)), 0, "checksum");
+
+        let edid = Edid::parse(&DELL_U2415).unwrap();
+        assert_eq!(edid, Edid {
+            manufacturer: "DEL".to_string(),
+            product_code: "a0c4".to_string(),
+            model: "DELL U2415".to_string(),
+            // the serial string descriptor wins over the numeric serial
+            serial: "7MT0184R1BPL".to_string(),
+            width: 1920,
+            height: 1200,
+        });
+        assert_eq!(edid.resolution(), "1920x1200");
+    }
+
+    #[test]
+    fn parse_edid_without_descriptors() {
+        let mut data = DELL_U2415;
+        // drop the serial and name descriptors
+        data[72..108].fill(0);
+        data[75] = 0x10;
+        data[93] = 0x10;
+
+        let edid = Edid::parse(&data).unwrap();
+        assert_eq!(edid.model, "a0c4");
+        assert_eq!(edid.serial, 0x30314b4cu32.to_string());
+        assert_eq!(edid.resolution(), "1920x1200");
+    }
+
+    #[test]
+    fn reject_invalid_edid() {
+        assert!(Edid::parse(&DELL_U2415[..127]).is_none());
+        assert!(Edid::parse(&[0u8; 128]).is_none());
+        assert!(Edid::parse(&[]).is_none());
+    }
+
+    #[test]
+    fn connector_names() {
+        assert_eq!(split_connector("card0-HDMI-A-1"), Some(("card0", "HDMI-A-1")));
+        assert_eq!(split_connector("card1-DP-1"), Some(("card1", "DP-1")));
+        assert_eq!(split_connector("card0"), None);
+        assert_eq!(split_connector("renderD128"), None);
+        assert_eq!(split_connector("version"), None);
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::Result;
+use std::{collections::{HashMap, HashSet}, fs, path::{Path, PathBuf}, sync::{Arc, Mutex}, time::Duration};
+use tokio::task;
+
+use crate::{
+    config::FanRule,
+    session::interruptible_sleep,
+    temperature::{read_hwmon_sensors, read_string, Sensor, HWMON_DIR},
+    utils::execute_command,
+};
+
+#[derive(Debug, Clone)]
+struct Fan {
+    /// sysfs file the reading comes from, unique unlike the chip name
+    path: PathBuf,
+    chip: String,
+    fan: String,
+    rpm: u32,
+    /// Alarm limits set in the driver (`fanN_min`, `fanN_max`), zero means unset
+    min: Option<u32>,
+    max: Option<u32>,
+}
+
+fn read_fans(dir: &Path) -> Vec<Fan> {
+    let Ok(chips) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut fans = Vec::new();
+    for chip in chips.flatten() {
+        let chip_path = chip.path();
+        let chip_name = read_string(&chip_path.join("name"))
+            .unwrap_or_else(|| chip.file_name().to_string_lossy().into_owned());
+
+        let Ok(files) = fs::read_dir(&chip_path) else {
+            continue;
+        };
+
+        for file in files.flatten() {
+            let file_name = file.file_name().to_string_lossy().into_owned();
+            let Some(prefix) = file_name.strip_suffix("_input").filter(|p| p.starts_with("fan")) else {
+                continue;
+            };
+            let read_rpm = |path: &Path| read_string(path).and_then(|s| s.parse::<u32>().ok());
+            let Some(rpm) = read_rpm(&file.path()) else {
+                continue;
+            };
+            let limit = |name: &str| read_rpm(&chip_path.join(format!("{}_{}", prefix, name))).filter(|&rpm| rpm > 0);
+
+            fans.push(Fan {
+                path: file.path(),
+                chip: chip_name.clone(),
+                fan: read_string(&chip_path.join(format!("{}_label", prefix)))
+                    .unwrap_or_else(|| prefix.to_string()),
+                rpm,
+                min: limit("min"),
+                max: limit("max"),
+            });
+        }
+    }
+
+    fans
+}
+
+/// Hottest sensor of the fan's chip, or of the whole system when the chip
+/// has no temperature sensors (e.g. a dedicated fan controller).
+fn hottest_sensor<'a>(sensors: &'a [Sensor], fan: &Fan) -> Option<&'a Sensor> {
+    let hottest = |chip: Option<&Path>| {
+        sensors
+            .iter()
+            .filter(|sensor| chip.is_none_or(|chip| sensor.path.parent() == Some(chip)))
+            .max_by(|a, b| a.temp.total_cmp(&b.temp))
+    };
+
+    hottest(fan.path.parent()).or_else(|| hottest(None))
+}
+
+/// RPM bounds of the rule, or the fan's own alarm limits when it sets none.
+fn bounds(rule: &FanRule, fan: &Fan) -> (Option<u32>, Option<u32>) {
+    if rule.min.is_some() || rule.max.is_some() {
+        (rule.min, rule.max)
+    } else {
+        (fan.min, fan.max)
+    }
+}
+
+fn rule_matches(rule: &FanRule, fan: &Fan, sensor: Option<&Sensor>) -> bool {
+    // without any bounds the rule looks for a stopped fan
+    let rpm_matches = match bounds(rule, fan) {
+        (None, None) => fan.rpm == 0,
+        (min, max) => min.is_some_and(|min| fan.rpm < min) || max.is_some_and(|max| fan.rpm > max),
+    };
+
+    let temp_matches = rule.temp.is_none_or(|temp| sensor.is_some_and(|sensor| sensor.temp >= temp));
+
+    rpm_matches && temp_matches
+}
+
+pub async fn monitor_fans(rules: Vec<FanRule>, sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    loop {
+        let fans = read_fans(Path::new(HWMON_DIR));
+        let sensors = read_hwmon_sensors(Path::new(HWMON_DIR));
+
+        for fan in &fans {
+            let sensor = hottest_sensor(&sensors, fan);
+
+            for (index, rule) in rules.iter().enumerate()
+                .filter(|(_, rule)| rule.chip.as_ref().is_none_or(|matcher| matcher.matches(&fan.chip)))
+                .filter(|(_, rule)| rule.fan.as_ref().is_none_or(|matcher| matcher.matches(&fan.fan)))
+            {
+                let should_notify = {
+                    let key = format!("fan-{}-{}", index, fan.path.display());
+                    let mut sent_guard = sent.lock().unwrap();
+
+                    if rule_matches(rule, fan, sensor) {
+                        sent_guard.insert(key)
+                    } else {
+                        sent_guard.remove(&key);
+                        false
+                    }
+                };
+
+                if should_notify {
+                    let (min, max) = bounds(rule, fan);
+
+                    let mut fields = HashMap::new();
+                    fields.insert("chip",   fan.chip.clone());
+                    fields.insert("fan",    fan.fan.clone());
+                    fields.insert("rpm",    fan.rpm.to_string());
+                    fields.insert("min",    min.map(|v| v.to_string()).unwrap_or_default());
+                    fields.insert("max",    max.map(|v| v.to_string()).unwrap_or_default());
+                    fields.insert("sensor", sensor.map(|s| s.sensor.clone()).unwrap_or_default());
+                    fields.insert("temp",   sensor.map(|s| format!("{:.1}", s.temp)).unwrap_or_default());
+
+                    let rule_clone = rule.clone();
+                    let _ = execute_command(rule_clone.message.exec.as_ref());
+                    task::spawn_blocking(move || {
+                        let _ = rule_clone.message.notify(&fields);
+                    })
+                    .await?;
+                }
+            }
+        }
+
+        interruptible_sleep(Duration::from_secs(10)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, file: &str, value: &str) {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, format!("{}\n", value)).unwrap();
+    }
+
+    fn fan(rpm: u32, min: Option<u32>, max: Option<u32>) -> Fan {
+        Fan {
+            path: PathBuf::from("/sys/class/hwmon/hwmon3/fan1_input"),
+            chip: "nct6775".to_string(),
+            fan: "fan1".to_string(),
+            rpm,
+            min,
+            max,
+        }
+    }
+
+    fn sensor(temp: f32) -> Sensor {
+        Sensor {
+            path: PathBuf::from("/sys/class/hwmon/hwmon3/temp1_input"),
+            chip: "nct6775".to_string(),
+            sensor: "SYSTIN".to_string(),
+            temp,
+            crit: None,
+            max: None,
+        }
+    }
+
+    #[test]
+    fn rpm_thresholds_table() {
+        let rule = |min, max| FanRule { min, max, ..Default::default() };
+
+        let cases = [
+            // stalled fans, without any bounds
+            (rule(None, None),             fan(0, None, None),             true),
+            (rule(None, None),             fan(900, None, None),           false),
+            // configured bounds
+            (rule(Some(500), None),        fan(499, None, None),           true),
+            (rule(Some(500), None),        fan(500, None, None),           false),
+            (rule(None, Some(3000)),       fan(3001, None, None),          true),
+            (rule(None, Some(3000)),       fan(0, None, None),             false),
+            (rule(Some(500), Some(3000)),  fan(1200, None, None),          false),
+            (rule(Some(500), Some(3000)),  fan(3500, None, None),          true),
+            // the driver's fanN_min and fanN_max apply without configured bounds
+            (rule(None, None),             fan(300, Some(400), None),      true),
+            (rule(None, None),             fan(0, Some(400), None),        true),
+            (rule(None, None),             fan(5000, None, Some(4800)),    true),
+            (rule(None, None),             fan(1200, Some(400), Some(4800)), false),
+            // and are ignored once the rule sets either bound
+            (rule(None, Some(6000)),       fan(300, Some(400), None),      false),
+            (rule(Some(200), None),        fan(5000, None, Some(4800)),    false),
+        ];
+
+        for (rule, fan, expected) in cases {
+            assert_eq!(rule_matches(&rule, &fan, None), expected, "{:?}/{:?} against {:?}", rule.min, rule.max, fan);
+        }
+    }
+
+    #[test]
+    fn temperature_condition() {
+        let rule = FanRule { temp: Some(70.0), ..Default::default() };
+
+        assert!(rule_matches(&rule, &fan(0, None, None), Some(&sensor(70.0))));
+        assert!(!rule_matches(&rule, &fan(0, None, None), Some(&sensor(69.9))));
+        assert!(!rule_matches(&rule, &fan(0, None, None), None));
+        assert!(!rule_matches(&rule, &fan(1200, None, None), Some(&sensor(90.0))));
+    }
+
+    #[test]
+    fn driver_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "hwmon0/fan1_input", "350");
+        write(dir.path(), "hwmon0/fan1_min", "400");
+        write(dir.path(), "hwmon0/fan1_max", "0");
+        write(dir.path(), "hwmon0/fan2_input", "1100");
+
+        let mut fans = read_fans(dir.path());
+        fans.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!((fans[0].rpm, fans[0].min, fans[0].max), (350, Some(400), None));
+        assert_eq!((fans[1].rpm, fans[1].min, fans[1].max), (1100, None, None));
+    }
+
+    #[test]
+    fn hottest_sensor_of_the_same_hwmon_device() {
+        let dir = tempfile::tempdir().unwrap();
+        // two chips with the same name, e.g. a pair of nvme drives
+        write(dir.path(), "hwmon0/name", "nvme");
+        write(dir.path(), "hwmon0/temp1_input", "45000");
+        write(dir.path(), "hwmon0/temp2_input", "52000");
+        write(dir.path(), "hwmon0/fan1_input", "0");
+        write(dir.path(), "hwmon1/name", "nvme");
+        write(dir.path(), "hwmon1/temp1_input", "71000");
+        // a dedicated fan controller without sensors
+        write(dir.path(), "hwmon2/name", "thinkpad");
+        write(dir.path(), "hwmon2/fan1_input", "2400");
+        write(dir.path(), "hwmon2/fan1_label", "CPU fan");
+
+        let sensors = read_hwmon_sensors(dir.path());
+        let mut fans = read_fans(dir.path());
+        fans.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(fans.len(), 2);
+
+        assert_eq!(fans[0].fan, "fan1");
+        assert_eq!(fans[0].rpm, 0);
+        // the hotter nvme belongs to another device
+        let sensor = hottest_sensor(&sensors, &fans[0]).unwrap();
+        assert_eq!(sensor.path, dir.path().join("hwmon0/temp2_input"));
+        assert_eq!(sensor.temp, 52.0);
+
+        assert_eq!(fans[1].fan, "CPU fan");
+        assert_eq!(fans[1].rpm, 2400);
+        // falls back to the hottest sensor of the system
+        let sensor = hottest_sensor(&sensors, &fans[1]).unwrap();
+        assert_eq!(sensor.path, dir.path().join("hwmon1/temp1_input"));
+
+        assert!(hottest_sensor(&[], &fans[1]).is_none());
+    }
+}
@@ -0,0 +1,266 @@
+use anyhow::Result;
+use regex::Regex;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    process::Stdio,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader},
+    process::Command,
+    task,
+};
+
+use crate::{
+    config::JournalRule,
+    kmsg::{priority_matches, PRIORITIES},
+    matcher::Matcher,
+    utils::{capture_fields, execute_command, RuleRateLimits},
+};
+
+/// Reads one entry of journalctl's export format: `KEY=value` lines, or for
+/// binary values the key, a little-endian u64 length, the data and a newline.
+/// Entries are separated by an empty line.
+async fn read_entry<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<HashMap<String, String>>> {
+    let mut entry = HashMap::new();
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line).await? == 0 {
+            return Ok(None);
+        }
+
+        let field = line.strip_suffix(b"\n").unwrap_or(&line);
+        if field.is_empty() {
+            if entry.is_empty() {
+                continue;
+            }
+            return Ok(Some(entry));
+        }
+
+        match field.iter().position(|&b| b == b'=') {
+            Some(eq) => {
+                entry.insert(
+                    String::from_utf8_lossy(&field[..eq]).into_owned(),
+                    String::from_utf8_lossy(&field[eq + 1..]).into_owned(),
+                );
+            }
+            None => {
+                let name = String::from_utf8_lossy(field).into_owned();
+
+                let mut size = [0u8; 8];
+                reader.read_exact(&mut size).await?;
+                let mut data = vec![0u8; u64::from_le_bytes(size) as usize];
+                reader.read_exact(&mut data).await?;
+                let mut newline = [0u8; 1];
+                reader.read_exact(&mut newline).await?;
+
+                entry.insert(name, String::from_utf8_lossy(&data).into_owned());
+            }
+        }
+    }
+}
+
+fn entry_unit(entry: &HashMap<String, String>) -> &str {
+    entry.get("_SYSTEMD_USER_UNIT")
+        .or_else(|| entry.get("_SYSTEMD_UNIT"))
+        .map(String::as_str)
+        .unwrap_or_default()
+}
+
+fn rule_matches(rule: &JournalRule, entry: &HashMap<String, String>, priority: u8) -> bool {
+    let identifier = entry.get("SYSLOG_IDENTIFIER").map(String::as_str).unwrap_or_default();
+
+    rule.unit.as_ref().is_none_or(|matcher| matcher.matches(entry_unit(entry)))
+        && rule.identifier.as_ref().is_none_or(|matcher| matcher.matches(identifier))
+        && priority_matches(rule.priority.as_ref(), priority)
+}
+
+/// The exact values of all matchers, `None` when one is unset or open.
+fn all_literals<'a>(matchers: impl Iterator<Item = Option<&'a Matcher>>) -> Option<BTreeSet<&'a str>> {
+    let mut values = BTreeSet::new();
+    for matcher in matchers {
+        values.extend(matcher?.literals()?);
+    }
+    Some(values)
+}
+
+/// journalctl arguments narrowing the stream down to entries some rule may
+/// match. journalctl requires all of its filters to match, so each one is
+/// only passed when every rule sets it; the rules are still checked here.
+fn journalctl_filters(rules: &[JournalRule]) -> Vec<String> {
+    let mut args = Vec::new();
+
+    if let Some(units) = all_literals(rules.iter().map(|rule| rule.unit.as_ref())) {
+        for unit in units {
+            args.push(format!("--unit={}", unit));
+            args.push(format!("--user-unit={}", unit));
+        }
+    }
+    if let Some(identifiers) = all_literals(rules.iter().map(|rule| rule.identifier.as_ref())) {
+        args.extend(identifiers.into_iter().map(|identifier| format!("--identifier={}", identifier)));
+    }
+
+    let priorities = rules
+        .iter()
+        .map(|rule| PRIORITIES.iter().position(|p| Some(*p) == rule.priority.as_deref()))
+        .collect::<Option<Vec<_>>>();
+    if let Some(least_severe) = priorities.and_then(|priorities| priorities.into_iter().max()) {
+        args.push(format!("--priority={}", PRIORITIES[least_severe]));
+    }
+
+    args
+}
+
+pub async fn monitor_journal(rules: Vec<JournalRule>, _sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let regexes = rules
+        .iter()
+        .map(|rule| rule.pattern.as_deref().map(Regex::new).transpose())
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut limits = RuleRateLimits::new(rules.len());
+
+    let mut child = Command::new("journalctl")
+        .args(["--follow", "--lines=0", "--output=export"])
+        .args(journalctl_filters(&rules))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let Some(stdout) = child.stdout.take() else {
+        return Ok(());
+    };
+
+    let mut reader = BufReader::new(stdout);
+    while let Some(entry) = read_entry(&mut reader).await? {
+        let message = entry.get("MESSAGE").cloned().unwrap_or_default();
+        let priority = entry.get("PRIORITY").and_then(|p| p.parse().ok()).unwrap_or(6);
+
+        for (index, rule) in rules.iter().enumerate() {
+            if !rule_matches(rule, &entry, priority) {
+                continue;
+            }
+
+            let mut fields = match &regexes[index] {
+                Some(regex) => match regex.captures(&message) {
+                    Some(caps) => capture_fields(regex, &caps),
+                    None => continue,
+                },
+                None => HashMap::new(),
+            };
+
+            let Some(count) = limits.hit(index, rule.interval) else {
+                continue;
+            };
+
+            fields.insert("message".to_string(),    message.clone());
+            fields.insert("unit".to_string(),       entry_unit(&entry).to_string());
+            fields.insert("identifier".to_string(), entry.get("SYSLOG_IDENTIFIER").cloned().unwrap_or_default());
+            fields.insert("pid".to_string(),        entry.get("_PID").cloned().unwrap_or_default());
+            fields.insert("priority".to_string(),   PRIORITIES.get(priority as usize).copied().unwrap_or("unknown").to_string());
+            fields.insert("count".to_string(),      count.to_string());
+
+            let rule_clone = rule.clone();
+            let _ = execute_command(rule_clone.message.exec.as_ref());
+            task::spawn_blocking(move || {
+                let _ = rule_clone.message.notify(
+                    &fields
+                        .iter()
+                        .map(|(k, v)| (k.as_str(), v.clone()))
+                        .collect(),
+                );
+            })
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binary_field(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut field = format!("{}\n", name).into_bytes();
+        field.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        field.extend_from_slice(data);
+        field.push(b'\n');
+        field
+    }
+
+    #[tokio::test]
+    async fn export_stream() {
+        let mut stream = b"\n__CURSOR=s=1;i=1\nPRIORITY=3\n_SYSTEMD_UNIT=backup.service\nSYSLOG_IDENTIFIER=restic\nMESSAGE=repository locked\n\n".to_vec();
+        stream.extend_from_slice(b"PRIORITY=6\n_SYSTEMD_USER_UNIT=app.service\n");
+        // multi-line messages and non-UTF-8 data are exported in the binary form
+        stream.extend(binary_field("MESSAGE", b"first line\nsecond line=x"));
+        stream.extend(binary_field("BLOB", &[0xff, 0x00, b'\n']));
+        stream.extend_from_slice(b"_PID=42\n\n");
+        stream.extend_from_slice(b"MESSAGE=truncated");
+
+        let mut reader = BufReader::new(stream.as_slice());
+
+        let entry = read_entry(&mut reader).await.unwrap().unwrap();
+        assert_eq!(entry["MESSAGE"], "repository locked");
+        assert_eq!(entry["__CURSOR"], "s=1;i=1");
+        assert_eq!(entry_unit(&entry), "backup.service");
+
+        let entry = read_entry(&mut reader).await.unwrap().unwrap();
+        assert_eq!(entry["MESSAGE"], "first line\nsecond line=x");
+        assert_eq!(entry["BLOB"], "\u{fffd}\0\n");
+        assert_eq!(entry["_PID"], "42");
+        // user units take precedence over the user manager's system unit
+        assert_eq!(entry_unit(&entry), "app.service");
+
+        // an entry cut off by the end of the stream is dropped
+        assert!(read_entry(&mut reader).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn filters_passed_to_journalctl() {
+        let rule = |unit: Option<&[&str]>, identifier: Option<&[&str]>, priority: Option<&str>| JournalRule {
+            unit: unit.map(|sources| Matcher::new(sources).unwrap()),
+            identifier: identifier.map(|sources| Matcher::new(sources).unwrap()),
+            priority: priority.map(str::to_string),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            journalctl_filters(&[
+                rule(Some(&["backup.service"]), None, Some("err")),
+                rule(Some(&["sshd.service", "backup.service"]), Some(&["sshd"]), Some("warning")),
+            ]),
+            [
+                "--unit=backup.service", "--user-unit=backup.service",
+                "--unit=sshd.service", "--user-unit=sshd.service",
+                "--priority=warning",
+            ]
+        );
+        assert_eq!(
+            journalctl_filters(&[
+                rule(None, Some(&["sshd"]), Some("err")),
+                rule(Some(&["backup.service"]), Some(&["restic", "sshd"]), None),
+            ]),
+            ["--identifier=restic", "--identifier=sshd"]
+        );
+        // patterns journalctl can't express leave the whole journal to the rules
+        assert!(journalctl_filters(&[rule(Some(&["backup-*.service"]), Some(&["!sshd"]), None)]).is_empty());
+        assert!(journalctl_filters(&[rule(None, None, None), rule(Some(&["backup.service"]), None, None)]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn truncated_binary_field() {
+        let mut stream = b"MESSAGE\n".to_vec();
+        stream.extend_from_slice(&100u64.to_le_bytes());
+        stream.extend_from_slice(b"short");
+
+        let mut reader = BufReader::new(stream.as_slice());
+        assert!(read_entry(&mut reader).await.is_err());
+    }
+}
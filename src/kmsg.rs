@@ -0,0 +1,218 @@
+use anyhow::Result;
+use regex::Regex;
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+    os::unix::fs::OpenOptionsExt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::{unix::AsyncFd, Interest},
+    task,
+};
+
+use crate::{config::KernelLogRule, utils::{capture_fields, execute_command, RuleRateLimits}};
+
+pub const KMSG_PATH: &str = "/dev/kmsg";
+
+pub const PRIORITIES: &[&str] = &["emerg", "alert", "crit", "err", "warning", "notice", "info", "debug"];
+pub const FACILITIES: &[&str] = &[
+    "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news",
+    "uucp", "cron", "authpriv", "ftp", "ntp", "security", "console", "solaris-cron",
+    "local0", "local1", "local2", "local3", "local4", "local5", "local6", "local7",
+];
+
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub priority: u8,
+    pub facility: u8,
+    pub seq: u64,
+    /// Time since boot
+    pub timestamp: Duration,
+    pub message: String,
+}
+
+impl Record {
+    /// Parses "prefix,seq,timestamp,flags[,...];message" followed by
+    /// optional " KEY=value" continuation lines, which are dropped.
+    pub fn parse(raw: &str) -> Option<Self> {
+        let (header, rest) = raw.split_once(';')?;
+        let mut header = header.split(',');
+        let prefix: u32 = header.next()?.parse().ok()?;
+        let seq = header.next()?.parse().ok()?;
+        let timestamp = Duration::from_micros(header.next()?.parse().ok()?);
+
+        Some(Self {
+            priority: (prefix & 7) as u8,
+            facility: (prefix >> 3) as u8,
+            seq,
+            timestamp,
+            message: rest.lines().next().unwrap_or_default().to_string(),
+        })
+    }
+
+    pub fn priority_name(&self) -> &'static str {
+        PRIORITIES.get(self.priority as usize).copied().unwrap_or("unknown")
+    }
+
+    pub fn facility_name(&self) -> &'static str {
+        FACILITIES.get(self.facility as usize).copied().unwrap_or("unknown")
+    }
+}
+
+/// Reader for records logged to the kernel ring buffer after it was opened.
+/// The device is polled through the reactor, a blocking read would occupy a
+/// thread of the blocking pool for as long as the reader lives.
+pub struct Kmsg {
+    file: AsyncFd<File>,
+    buffer: Vec<u8>,
+}
+
+impl Kmsg {
+    pub async fn open() -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(KMSG_PATH)?;
+        file.seek(SeekFrom::End(0))?;
+
+        Ok(Self {
+            file: AsyncFd::with_interest(file, Interest::READABLE)?,
+            buffer: vec![0u8; 8192],
+        })
+    }
+
+    pub async fn next_record(&mut self) -> Result<Option<Record>> {
+        loop {
+            let mut guard = self.file.readable().await?;
+
+            // every read returns exactly one record
+            let read = match guard.try_io(|file| file.get_ref().read(&mut self.buffer)) {
+                Err(_would_block) => continue,
+                Ok(Ok(0)) => return Ok(None),
+                Ok(Ok(read)) => read,
+                // the ring buffer overwrote records we haven't read yet
+                Ok(Err(e)) if e.kind() == ErrorKind::BrokenPipe => continue,
+                Ok(Err(e)) => return Err(e.into()),
+            };
+
+            if let Some(record) = Record::parse(&String::from_utf8_lossy(&self.buffer[..read])) {
+                return Ok(Some(record));
+            }
+        }
+    }
+}
+
+/// Whether `priority` is at least as severe as the named one.
+pub fn priority_matches(name: Option<&String>, priority: u8) -> bool {
+    name.and_then(|name| PRIORITIES.iter().position(|p| p == name))
+        .is_none_or(|max| priority as usize <= max)
+}
+
+fn rule_matches(rule: &KernelLogRule, record: &Record) -> bool {
+    priority_matches(rule.priority.as_ref(), record.priority)
+        && rule.facility.as_ref().is_none_or(|name| name == record.facility_name())
+}
+
+pub async fn monitor_kernel_log(rules: Vec<KernelLogRule>, _sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let regexes = rules
+        .iter()
+        .map(|rule| Regex::new(&rule.pattern))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut limits = RuleRateLimits::new(rules.len());
+
+    let mut kmsg = Kmsg::open().await?;
+    while let Some(record) = kmsg.next_record().await? {
+        for (index, rule) in rules.iter().enumerate() {
+            if !rule_matches(rule, &record) {
+                continue;
+            }
+            let Some(caps) = regexes[index].captures(&record.message) else {
+                continue;
+            };
+
+            let Some(count) = limits.hit(index, rule.interval) else {
+                continue;
+            };
+
+            let mut fields = capture_fields(&regexes[index], &caps);
+            fields.insert("message".to_string(),   record.message.clone());
+            fields.insert("priority".to_string(),  record.priority_name().to_string());
+            fields.insert("facility".to_string(),  record.facility_name().to_string());
+            fields.insert("timestamp".to_string(), format!("{:.6}", record.timestamp.as_secs_f64()));
+            fields.insert("seq".to_string(),       record.seq.to_string());
+            fields.insert("count".to_string(),     count.to_string());
+
+            let rule_clone = rule.clone();
+            let _ = execute_command(rule_clone.message.exec.as_ref());
+            task::spawn_blocking(move || {
+                let _ = rule_clone.message.notify(
+                    &fields
+                        .iter()
+                        .map(|(k, v)| (k.as_str(), v.clone()))
+                        .collect(),
+                );
+            })
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_splits_into_facility_and_priority() {
+        let record = Record::parse("6,1234,5678901,-;usb 1-2: new high-speed USB device number 3 using xhci_hcd").unwrap();
+        assert_eq!((record.facility_name(), record.priority_name()), ("kern", "info"));
+        assert_eq!(record.seq, 1234);
+        assert_eq!(record.timestamp, Duration::from_micros(5678901));
+        assert_eq!(record.message, "usb 1-2: new high-speed USB device number 3 using xhci_hcd");
+
+        // facility 3 (daemon), priority 3 (err)
+        let record = Record::parse("27,40,100,-;systemd[1]: Failed to start unit").unwrap();
+        assert_eq!((record.facility_name(), record.priority_name()), ("daemon", "err"));
+
+        // facility 23 (local7), priority 7 (debug)
+        let record = Record::parse("191,41,100,-;debug").unwrap();
+        assert_eq!((record.facility, record.priority), (23, 7));
+    }
+
+    #[test]
+    fn continuation_lines_are_dropped() {
+        let raw = "3,523,12345678,-,caller=T42;sd 2:0:0:0: [sdb] tag#0 FAILED Result: hostbyte=DID_OK; driverbyte=DRIVER_OK\n \
+                   SUBSYSTEM=scsi\n \
+                   DEVICE=+scsi:2:0:0:0\n";
+        let record = Record::parse(raw).unwrap();
+
+        assert_eq!(record.priority_name(), "err");
+        assert_eq!(record.seq, 523);
+        // only the first ';' separates the header, the message keeps the rest
+        assert_eq!(record.message, "sd 2:0:0:0: [sdb] tag#0 FAILED Result: hostbyte=DID_OK; driverbyte=DRIVER_OK");
+    }
+
+    #[test]
+    fn malformed_records() {
+        assert!(Record::parse("no header").is_none());
+        assert!(Record::parse("x,1,2,-;message").is_none());
+        assert!(Record::parse("6,1;message").is_none());
+    }
+
+    #[test]
+    fn priority_threshold() {
+        let warning = "warning".to_string();
+        assert!(priority_matches(Some(&warning), 3));
+        assert!(priority_matches(Some(&warning), 4));
+        assert!(!priority_matches(Some(&warning), 6));
+        assert!(priority_matches(None, 7));
+    }
+}
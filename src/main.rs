@@ -1,9 +1,23 @@
 mod message;
 mod config;
+mod bandwidth;
 mod battery;
 mod cpu;
+mod display;
+mod fan;
+mod journal;
+mod kmsg;
+mod matcher;
 mod memory;
+mod netlink;
+mod network;
+mod oom;
+mod probe;
+mod process;
+mod session;
 mod storage;
+mod systemd;
+mod temperature;
 mod udev;
 mod utils;
 
@@ -15,10 +29,22 @@ use log::{error, info};
 
 use crate::{
     config::get_config,
+    bandwidth::monitor_bandwidth,
     battery::monitor_battery,
     cpu::monitor_cpu,
+    fan::monitor_fans,
+    journal::monitor_journal,
+    kmsg::monitor_kernel_log,
     memory::monitor_memory,
+    netlink::monitor_links,
+    network::{monitor_network, monitor_wifi},
+    oom::monitor_oom,
+    probe::monitor_probes,
+    process::monitor_processes,
+    session::monitor_session,
     storage::monitor_storage,
+    systemd::monitor_systemd_units,
+    temperature::monitor_temperature,
     udev::listen_udev,
 };
 
@@ -50,19 +76,46 @@ async fn main() -> Result<()> {
 
 
 fn start_tasks(config: crate::config::Config, sent: SharedNotificationSet) -> TaskHandles {
+    let battery_backend = config.battery_backend;
     let battery_config = config.battery.clone();
     let cpu_config = config.cpu.clone();
     let memory_config = config.memory.clone();
     let storage_config = config.storage.clone();
+    let network_config = config.network.clone();
+    let wifi_config = config.wifi.clone();
+    let link_config = config.link.clone();
+    let bandwidth_config = config.bandwidth.clone();
+    let probe_config = config.probe.clone();
+    let temperature_config = config.temperature.clone();
+    let fan_config = config.fan.clone();
+    let process_config = config.process.clone();
+    let oom_config = config.oom.clone();
+    let kernel_log_config = config.kernel_log.clone();
+    let journal_config = config.journal.clone();
+    let systemd_unit_config = config.systemd_unit.clone();
+    let session_config = config.session.clone();
 
     let sent1 = sent.clone();
     let sent2 = sent.clone();
     let sent3 = sent.clone();
     let sent4 = sent.clone();
+    let sent5 = sent.clone();
+    let sent6 = sent.clone();
+    let sent7 = sent.clone();
+    let sent8 = sent.clone();
+    let sent9 = sent.clone();
+    let sent10 = sent.clone();
+    let sent11 = sent.clone();
+    let sent12 = sent.clone();
+    let sent13 = sent.clone();
+    let sent14 = sent.clone();
+    let sent15 = sent.clone();
+    let sent16 = sent.clone();
+    let sent17 = sent.clone();
 
     vec![
         spawn(async move {
-            if let Err(e) = monitor_battery(battery_config, sent1).await {
+            if let Err(e) = monitor_battery(battery_backend, battery_config, sent1).await {
                 error!("Battery monitor failed: {}", e);
             }
         }),
@@ -81,6 +134,71 @@ fn start_tasks(config: crate::config::Config, sent: SharedNotificationSet) -> Ta
                 error!("Storage monitor failed: {}", e);
             }
         }),
+        spawn(async move {
+            if let Err(e) = monitor_network(network_config, sent5).await {
+                error!("Network monitor failed: {}", e);
+            }
+        }),
+        spawn(async move {
+            if let Err(e) = monitor_wifi(wifi_config, sent6).await {
+                error!("Wifi monitor failed: {}", e);
+            }
+        }),
+        spawn(async move {
+            if let Err(e) = monitor_links(link_config, sent7).await {
+                error!("Link monitor failed: {}", e);
+            }
+        }),
+        spawn(async move {
+            if let Err(e) = monitor_bandwidth(bandwidth_config, sent8).await {
+                error!("Bandwidth monitor failed: {}", e);
+            }
+        }),
+        spawn(async move {
+            if let Err(e) = monitor_probes(probe_config, sent9).await {
+                error!("Probe monitor failed: {}", e);
+            }
+        }),
+        spawn(async move {
+            if let Err(e) = monitor_temperature(temperature_config, sent10).await {
+                error!("Temperature monitor failed: {}", e);
+            }
+        }),
+        spawn(async move {
+            if let Err(e) = monitor_fans(fan_config, sent11).await {
+                error!("Fan monitor failed: {}", e);
+            }
+        }),
+        spawn(async move {
+            if let Err(e) = monitor_processes(process_config, sent12).await {
+                error!("Process monitor failed: {}", e);
+            }
+        }),
+        spawn(async move {
+            if let Err(e) = monitor_oom(oom_config, sent13).await {
+                error!("OOM monitor failed: {}", e);
+            }
+        }),
+        spawn(async move {
+            if let Err(e) = monitor_kernel_log(kernel_log_config, sent14).await {
+                error!("Kernel log monitor failed: {}", e);
+            }
+        }),
+        spawn(async move {
+            if let Err(e) = monitor_journal(journal_config, sent15).await {
+                error!("Journal monitor failed: {}", e);
+            }
+        }),
+        spawn(async move {
+            if let Err(e) = monitor_systemd_units(systemd_unit_config, sent16).await {
+                error!("Systemd unit monitor failed: {}", e);
+            }
+        }),
+        spawn(async move {
+            if let Err(e) = monitor_session(battery_backend, session_config, sent17).await {
+                error!("Session monitor failed: {}", e);
+            }
+        }),
     ]
 }
 
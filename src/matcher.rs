@@ -0,0 +1,263 @@
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer};
+use std::fmt;
+
+use crate::utils::glob_match;
+
+const MISSING: &str = "<missing>";
+
+#[derive(Debug, Clone)]
+enum PatternKind {
+    Exact(String),
+    Glob(String),
+    Regex(Regex),
+    /// `<missing>`, matches a field that has no value at all
+    Missing,
+}
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    source: String,
+    negated: bool,
+    kind: PatternKind,
+}
+
+impl Pattern {
+    /// `!` negates a pattern, `re:` introduces a regex, `<missing>` stands for
+    /// an absent value, `*` and `?` make it a glob and anything else is
+    /// compared exactly.
+    fn parse(source: &str, ignore_case: bool) -> Result<Self, regex::Error> {
+        let (negated, pattern) = match source.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, source),
+        };
+
+        let kind = if pattern == MISSING {
+            PatternKind::Missing
+        } else if let Some(regex) = pattern.strip_prefix("re:") {
+            // like exact strings and globs, a regex has to match the whole value
+            let flags = if ignore_case { "(?i)" } else { "" };
+            PatternKind::Regex(Regex::new(&format!("{}^(?:{})$", flags, regex))?)
+        } else {
+            let pattern = if ignore_case { pattern.to_lowercase() } else { pattern.to_string() };
+            if pattern.contains(['*', '?']) {
+                PatternKind::Glob(pattern)
+            } else {
+                PatternKind::Exact(pattern)
+            }
+        };
+
+        Ok(Self { source: source.to_string(), negated, kind })
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        match &self.kind {
+            PatternKind::Exact(expect) => expect == text,
+            PatternKind::Glob(pattern) => glob_match(pattern, text),
+            PatternKind::Regex(regex)  => regex.is_match(text),
+            PatternKind::Missing       => false,
+        }
+    }
+}
+
+/// String matcher shared by rule fields, written as a single pattern or a
+/// list of them, e.g. `"sd*"`, `"re:nvme[0-9]+n1"` or `["sd*", "!sda"]`.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    patterns: Vec<Pattern>,
+    ignore_case: bool,
+}
+
+impl Matcher {
+    pub fn new<S: AsRef<str>>(sources: &[S]) -> Result<Self, regex::Error> {
+        Self::build(sources, false)
+    }
+
+    /// Like `new`, but compares without regard to ASCII case, used for
+    /// values such as hexadecimal ids.
+    pub fn new_ignore_case<S: AsRef<str>>(sources: &[S]) -> Result<Self, regex::Error> {
+        Self::build(sources, true)
+    }
+
+    fn build<S: AsRef<str>>(sources: &[S], ignore_case: bool) -> Result<Self, regex::Error> {
+        let patterns = sources
+            .iter()
+            .map(|source| Pattern::parse(source.as_ref(), ignore_case))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { patterns, ignore_case })
+    }
+
+    /// Matches when any of the plain patterns matches (or there are none)
+    /// and none of the negated ones does.
+    pub fn matches(&self, text: &str) -> bool {
+        let lowered;
+        let text = if self.ignore_case {
+            lowered = text.to_lowercase();
+            lowered.as_str()
+        } else {
+            text
+        };
+
+        let (negated, plain): (Vec<&Pattern>, Vec<&Pattern>) = self.patterns
+            .iter()
+            .partition(|pattern| pattern.negated);
+
+        (plain.is_empty() || plain.iter().any(|pattern| pattern.is_match(text)))
+            && !negated.iter().any(|pattern| pattern.is_match(text))
+    }
+
+    /// Like `matches`, for fields that may be absent. An absent value only
+    /// matches when `<missing>` is listed, e.g. `["usb-storage", "<missing>"]`.
+    pub fn matches_value(&self, value: Option<&str>) -> bool {
+        match value {
+            Some(text) => self.matches(text),
+            None => self.patterns
+                .iter()
+                .any(|pattern| !pattern.negated && matches!(pattern.kind, PatternKind::Missing)),
+        }
+    }
+
+    /// Values of the exact patterns, negated or not, used to validate fields
+    /// with a fixed set of names.
+    pub fn exact_values(&self) -> impl Iterator<Item = &str> {
+        self.patterns.iter().filter_map(|pattern| match &pattern.kind {
+            PatternKind::Exact(value) => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /// The exact values this matcher accepts, `None` when it contains a glob,
+    /// a regex or a negated pattern and the set of values is open.
+    pub fn literals(&self) -> Option<Vec<&str>> {
+        if self.patterns.is_empty() {
+            return None;
+        }
+
+        self.patterns
+            .iter()
+            .map(|pattern| match &pattern.kind {
+                PatternKind::Exact(value) if !pattern.negated => Some(value.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sources: Vec<&str> = self.patterns.iter().map(|pattern| pattern.source.as_str()).collect();
+        write!(f, "{}", sources.join(", "))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Sources {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Sources {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Sources::One(source) => vec![source],
+            Sources::Many(sources) => sources,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Matcher {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let sources = Sources::deserialize(deserializer)?.into_vec();
+        Matcher::new(&sources).map_err(de::Error::custom)
+    }
+}
+
+/// `deserialize_with` helper for optional fields matched without regard to case.
+pub fn ignore_case<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Matcher>, D::Error> {
+    let sources = Sources::deserialize(deserializer)?.into_vec();
+    Matcher::new_ignore_case(&sources).map(Some).map_err(de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(sources: &[&str]) -> Matcher {
+        Matcher::new(sources).unwrap()
+    }
+
+    #[test]
+    fn pattern_kinds() {
+        let cases: &[(&[&str], &str, bool)] = &[
+            (&["sda"],              "sda",   true),
+            (&["sda"],              "sda1",  false),
+            (&["sd*"],              "sda1",  true),
+            (&["sd?"],              "sda1",  false),
+            (&["re:nvme[0-9]+n1"],  "nvme0n1", true),
+            // regexes are anchored like exact strings and globs
+            (&["re:nvme[0-9]+n1"],  "nvme0n1p2", false),
+            (&["!sda"],             "sdb",   true),
+            (&["!sda"],             "sda",   false),
+            (&["sd*", "!sda"],      "sdb",   true),
+            (&["sd*", "!sda"],      "sda",   false),
+            (&["sd*", "nvme*"],     "nvme0n1", true),
+            (&["sd*", "nvme*"],     "mmcblk0", false),
+            (&["<missing>"],        "sda",   false),
+            (&["!<missing>"],       "sda",   true),
+        ];
+
+        for (sources, text, expected) in cases {
+            assert_eq!(matcher(sources).matches(text), *expected, "{:?} against {:?}", sources, text);
+        }
+    }
+
+    #[test]
+    fn absent_values() {
+        let cases: &[(&[&str], bool)] = &[
+            (&["usb-storage"],              false),
+            (&["*"],                        false),
+            (&["!usb-storage"],             false),
+            (&["<missing>"],                true),
+            (&["usb-storage", "<missing>"], true),
+            (&["!<missing>"],               false),
+        ];
+
+        for (sources, expected) in cases {
+            assert_eq!(matcher(sources).matches_value(None), *expected, "{:?}", sources);
+        }
+    }
+
+    #[test]
+    fn ignore_case() {
+        let ids = Matcher::new_ignore_case(&["046D", "re:0B[0-9]5"]).unwrap();
+        assert!(ids.matches("046d"));
+        assert!(ids.matches("046D"));
+        assert!(ids.matches("0b05"));
+        assert!(!matcher(&["046D"]).matches("046d"));
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(matcher(&["usb", "block"]).literals(), Some(vec!["usb", "block"]));
+        assert_eq!(matcher(&["usb", "!block"]).literals(), None);
+        assert_eq!(matcher(&["us*"]).literals(), None);
+        assert_eq!(matcher(&["<missing>"]).literals(), None);
+        assert_eq!(matcher(&["full", "!portal", "re:l.*"]).exact_values().collect::<Vec<_>>(), ["full", "portal"]);
+    }
+
+    #[test]
+    fn deserialize_string_or_list() {
+        #[derive(Deserialize)]
+        struct Rule {
+            one: Matcher,
+            many: Matcher,
+        }
+
+        let rule: Rule = toml::from_str("one = \"sd*\"\nmany = [\"sd*\", \"!sda\"]").unwrap();
+        assert_eq!(rule.one.to_string(), "sd*");
+        assert_eq!(rule.many.to_string(), "sd*, !sda");
+        assert!(toml::from_str::<Rule>("one = \"re:(\"\nmany = []").is_err());
+    }
+}
@@ -2,13 +2,14 @@ use anyhow::Result;
 use humansize::{format_size, DECIMAL};
 use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}, time::Duration};
 use sysinfo::System;
-use tokio::{task, time::sleep};
+use tokio::task;
 
-use crate::{config::MemoryRule, utils::execute_command};
+use crate::{config::MemoryRule, process, session::interruptible_sleep, utils::execute_command};
 
 pub async fn monitor_memory(rules: Vec<MemoryRule>, sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    let mut sys = System::new();
+
     loop {
-        let mut sys = System::new();
         sys.refresh_memory();
 
         let total        = sys.total_memory();
@@ -49,15 +50,26 @@ pub async fn monitor_memory(rules: Vec<MemoryRule>, sent: Arc<Mutex<HashSet<Stri
                 fields.insert("left_percent_full", free_percent.to_string());
                 fields.insert("left_percent",      (free_percent as u32).to_string());
 
+                let top_count = process::top_count(rule.top, &rule.message);
+                if top_count > 0 {
+                    process::refresh_usage(&mut sys);
+                }
+                let top = process::top_fields(&sys, top_count, false);
+
                 let rule_clone = rule.clone();
                 let _ = execute_command(rule_clone.message.exec.as_ref());
                 task::spawn_blocking(move || {
-                    let _ = rule_clone.message.notify(&fields);
+                    let _ = rule_clone.message.notify(
+                        &fields
+                            .into_iter()
+                            .chain(top.iter().map(|(k, v)| (k.as_str(), v.clone())))
+                            .collect(),
+                    );
                 })
                 .await?;
             }
         }
 
-        sleep(Duration::from_secs(10)).await;
+        interruptible_sleep(Duration::from_secs(10)).await;
     }
 }
@@ -12,7 +12,7 @@ static TEMPLATE_REGEX: OnceLock<Regex> = OnceLock::new();
 
 fn get_template_regex() -> &'static Regex {
     TEMPLATE_REGEX.get_or_init(|| {
-        Regex::new(r"\{([a-zA-Z0-9_]+)\}")
+        Regex::new(r"\{([a-zA-Z0-9_.]+)\}")
             .expect("Failed to compile template regex")
     })
 }
@@ -61,6 +61,20 @@ impl Message {
         .to_string()
     }
 
+    /// Names of all fields referenced by the templates of this message.
+    pub fn placeholders(&self) -> HashSet<&str> {
+        let templates = [Some(&self.appname), self.summary.as_ref(), self.body.as_ref(), self.exec.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .chain(self.hints.iter().map(|hint| hint.0.as_str()));
+
+        templates
+            .flat_map(|template| get_template_regex().captures_iter(template))
+            .filter_map(|caps| caps.get(1).map(|m| m.as_str()))
+            .collect()
+    }
+
     pub fn notify(&self, fields: &HashMap<&str, String>) -> Result<()> {
         let urgency = parse_urgency(&self.urgency);
         let mut notification = Notification::new();
@@ -118,7 +132,7 @@ impl MyHint {
         Self(Message::render_template(&self.0.clone(), fields))
     }
 
-    fn parse_components(&self) -> HintComponents {
+    fn parse_components(&self) -> HintComponents<'_> {
         let parts: Vec<&str> = self.0.rsplitn(3, ':').collect();
 
         match parts.as_slice() {
@@ -0,0 +1,242 @@
+use anyhow::Result;
+use futures_util::{StreamExt, TryStreamExt};
+use netlink_packet_core::NetlinkPayload;
+use netlink_packet_route::{
+    address::nlas::Nla as AddressNla,
+    constants::{AF_INET, AF_INET6, IFF_LOWER_UP, IFF_UP, RT_TABLE_MAIN},
+    link::nlas::Nla as LinkNla,
+    AddressMessage, LinkMessage, RouteMessage, RtnlMessage,
+};
+use netlink_sys::{AsyncSocket, SocketAddr};
+use rtnetlink::{
+    constants::{RTMGRP_IPV4_IFADDR, RTMGRP_IPV4_ROUTE, RTMGRP_IPV6_IFADDR, RTMGRP_IPV6_ROUTE, RTMGRP_LINK},
+    new_connection,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::{Arc, Mutex},
+};
+use tokio::task;
+
+use crate::{config::LinkRule, utils::execute_command};
+
+pub const LINK_EVENTS: &[&str] = &[
+    "up",
+    "down",
+    "carrier",
+    "carrier_lost",
+    "address_add",
+    "address_remove",
+    "default_route",
+];
+
+#[derive(Debug, Default, Clone)]
+struct LinkState {
+    name: String,
+    up: bool,
+    carrier: bool,
+}
+
+impl From<&LinkMessage> for LinkState {
+    fn from(link: &LinkMessage) -> Self {
+        let name = link.nlas
+            .iter()
+            .find_map(|nla| match nla {
+                LinkNla::IfName(name) => Some(name.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+        let flags = link.header.flags;
+
+        Self {
+            name,
+            up: flags & IFF_UP != 0,
+            carrier: flags & IFF_LOWER_UP != 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct LinkEvent {
+    event: &'static str,
+    interface: String,
+    fields: HashMap<&'static str, String>,
+}
+
+impl LinkEvent {
+    fn new(event: &'static str, interface: &str) -> Self {
+        Self {
+            event,
+            interface: interface.to_string(),
+            fields: HashMap::new(),
+        }
+    }
+}
+
+fn family_name(family: u16) -> &'static str {
+    match family {
+        AF_INET  => "ipv4",
+        AF_INET6 => "ipv6",
+        _        => "unknown",
+    }
+}
+
+fn ip_from_bytes(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4  => <[u8; 4]>::try_from(bytes).ok().map(|b| IpAddr::V4(Ipv4Addr::from(b))),
+        16 => <[u8; 16]>::try_from(bytes).ok().map(|b| IpAddr::V6(Ipv6Addr::from(b))),
+        _  => None,
+    }
+}
+
+fn link_events(previous: Option<&LinkState>, current: &LinkState) -> Vec<LinkEvent> {
+    let mut events = Vec::new();
+    let (was_up, had_carrier) = previous.map_or((false, false), |p| (p.up, p.carrier));
+
+    if current.up != was_up {
+        events.push(LinkEvent::new(if current.up { "up" } else { "down" }, &current.name));
+    }
+    if current.carrier != had_carrier {
+        events.push(LinkEvent::new(if current.carrier { "carrier" } else { "carrier_lost" }, &current.name));
+    }
+
+    events
+}
+
+fn address_event(event: &'static str, address: &AddressMessage, links: &HashMap<u32, LinkState>) -> LinkEvent {
+    let interface = links
+        .get(&address.header.index)
+        .map(|link| link.name.clone())
+        .unwrap_or_default();
+
+    // for point-to-point links the local address is the interface's own one
+    let ip = address.nlas
+        .iter()
+        .find_map(|nla| match nla {
+            AddressNla::Local(bytes) => ip_from_bytes(bytes),
+            _ => None,
+        })
+        .or_else(|| address.nlas.iter().find_map(|nla| match nla {
+            AddressNla::Address(bytes) => ip_from_bytes(bytes),
+            _ => None,
+        }));
+
+    let mut link_event = LinkEvent::new(event, &interface);
+    link_event.fields.insert("address",    ip.map(|ip| ip.to_string()).unwrap_or_default());
+    link_event.fields.insert("prefix_len", address.header.prefix_len.to_string());
+    link_event.fields.insert("family",     family_name(address.header.family as u16).to_string());
+    link_event
+}
+
+fn route_event(action: &str, route: &RouteMessage, links: &HashMap<u32, LinkState>) -> Option<LinkEvent> {
+    if route.header.destination_prefix_length != 0 || route.header.table != RT_TABLE_MAIN {
+        return None;
+    }
+
+    let interface = route
+        .output_interface()
+        .and_then(|index| links.get(&index))
+        .map(|link| link.name.clone())
+        .unwrap_or_default();
+
+    let mut link_event = LinkEvent::new("default_route", &interface);
+    link_event.fields.insert("action",  action.to_string());
+    link_event.fields.insert("gateway", route.gateway().map(|ip| ip.to_string()).unwrap_or_default());
+    link_event.fields.insert("family",  family_name(route.header.address_family as u16).to_string());
+    Some(link_event)
+}
+
+pub async fn monitor_links(rules: Vec<LinkRule>, _sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let (mut connection, handle, mut messages) = new_connection()?;
+    let groups = RTMGRP_LINK
+        | RTMGRP_IPV4_IFADDR
+        | RTMGRP_IPV6_IFADDR
+        | RTMGRP_IPV4_ROUTE
+        | RTMGRP_IPV6_ROUTE;
+    connection
+        .socket_mut()
+        .socket_mut()
+        .bind(&SocketAddr::new(0, groups))?;
+    task::spawn(connection);
+
+    let mut links: HashMap<u32, LinkState> = HashMap::new();
+    let mut dump = handle.link().get().execute();
+    while let Some(link) = dump.try_next().await? {
+        links.insert(link.header.index, LinkState::from(&link));
+    }
+
+    while let Some((message, _)) = messages.next().await {
+        let NetlinkPayload::InnerMessage(message) = message.payload else {
+            continue;
+        };
+
+        let events = match message {
+            RtnlMessage::NewLink(link) => {
+                let current = LinkState::from(&link);
+                let events = link_events(links.get(&link.header.index), &current);
+                links.insert(link.header.index, current);
+                events
+            }
+            RtnlMessage::DelLink(link) => {
+                let previous = links.remove(&link.header.index);
+                let current = LinkState {
+                    name: previous.as_ref().map(|p| p.name.clone()).unwrap_or_default(),
+                    ..Default::default()
+                };
+                link_events(previous.as_ref(), &current)
+            }
+            RtnlMessage::NewAddress(address) => vec![address_event("address_add", &address, &links)],
+            RtnlMessage::DelAddress(address) => vec![address_event("address_remove", &address, &links)],
+            RtnlMessage::NewRoute(route) => route_event("add", &route, &links).into_iter().collect(),
+            RtnlMessage::DelRoute(route) => route_event("remove", &route, &links).into_iter().collect(),
+            _ => continue,
+        };
+
+        for link_event in events {
+            for rule in rules.iter()
+                .filter(|rule| rule.event == link_event.event)
+                .filter(|rule| rule.interface.as_ref().is_none_or(|matcher| matcher.matches(&link_event.interface)))
+            {
+                let mut fields = link_event.fields.clone();
+                fields.insert("event",     link_event.event.to_string());
+                fields.insert("interface", link_event.interface.clone());
+
+                let rule_clone = rule.clone();
+                let _ = execute_command(rule_clone.message.exec.as_ref());
+                task::spawn_blocking(move || {
+                    let _ = rule_clone.message.notify(&fields);
+                })
+                .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(up: bool, carrier: bool) -> LinkState {
+        LinkState { name: "eth0".to_string(), up, carrier }
+    }
+
+    fn events(previous: Option<LinkState>, current: LinkState) -> Vec<&'static str> {
+        link_events(previous.as_ref(), &current).iter().map(|event| event.event).collect()
+    }
+
+    #[test]
+    fn carrier_is_independent_of_admin_state() {
+        assert_eq!(events(Some(state(true, true)), state(true, false)), ["carrier_lost"]);
+        assert_eq!(events(Some(state(true, false)), state(true, true)), ["carrier"]);
+        assert_eq!(events(Some(state(true, true)), state(false, false)), ["down", "carrier_lost"]);
+        assert_eq!(events(None, state(true, false)), ["up"]);
+        assert!(events(Some(state(true, true)), state(true, true)).is_empty());
+    }
+}
@@ -1,53 +1,425 @@
+use anyhow::Result;
 use futures_util::stream::StreamExt;
-use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}};
-use zbus::{Connection, MessageStream, zvariant::OwnedValue};
+use log::{debug, info, warn};
+use std::{collections::{HashMap, HashSet}, fs, sync::{Arc, Mutex}, time::Duration};
+use tokio::task;
+use zbus::{fdo::PropertiesProxy, zvariant::{ObjectPath, OwnedObjectPath}, Connection};
+use zbus_names::InterfaceName;
 
-use crate::config::Config;
+use crate::{config::{NetworkRule, WifiRule}, matcher::Matcher, session::interruptible_sleep, utils::execute_command};
+
+const NM_DESTINATION: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+const NM_INTERFACE: &str = "org.freedesktop.NetworkManager";
+const NM_ACTIVE_INTERFACE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+const NM_DEVICE_INTERFACE: &str = "org.freedesktop.NetworkManager.Device";
+const NM_WIRELESS_INTERFACE: &str = "org.freedesktop.NetworkManager.Device.Wireless";
+const NM_ACCESS_POINT_INTERFACE: &str = "org.freedesktop.NetworkManager.AccessPoint";
+const NM_DEVICE_TYPE_WIFI: u32 = 2;
+const PROC_WIRELESS: &str = "/proc/net/wireless";
+
+pub const STATE_NAMES: &[&str] = &[
+    "unknown",
+    "asleep",
+    "disconnected",
+    "disconnecting",
+    "connecting",
+    "limited",
+    "connected",
+];
+
+pub const WIFI_EVENTS: &[&str] = &[
+    "signal",
+    "roam",
+    "ssid",
+];
+
+pub const CONNECTIVITY_NAMES: &[&str] = &[
+    "unknown",
+    "none",
+    "portal",
+    "limited",
+    "full",
+];
+
+/// Maps `NMState` to a rule state name, local and site-only connections are
+/// reported as `limited`.
+fn state_name(state: u32) -> &'static str {
+    match state {
+        10 => "asleep",
+        20 => "disconnected",
+        30 => "disconnecting",
+        40 => "connecting",
+        50 | 60 => "limited",
+        70 => "connected",
+        _ => "unknown",
+    }
+}
+
+fn connectivity_name(connectivity: u32) -> &'static str {
+    match connectivity {
+        1 => "none",
+        2 => "portal",
+        3 => "limited",
+        4 => "full",
+        _ => "unknown",
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct ActiveConnection {
+    id: String,
+    connection_type: String,
+    device: String,
+}
+
+async fn properties_proxy(conn: &Connection, path: ObjectPath<'_>) -> Result<PropertiesProxy<'static>> {
+    let proxy = PropertiesProxy::builder(conn)
+        .destination(NM_DESTINATION)?
+        .path(path.into_owned())?
+        .build()
+        .await?;
+    Ok(proxy)
+}
+
+async fn active_connection(conn: &Connection, path: &OwnedObjectPath) -> Result<ActiveConnection> {
+    if path.as_str() == "/" {
+        return Ok(ActiveConnection::default());
+    }
+
+    let properties = properties_proxy(conn, path.as_ref()).await?;
+    let interface = InterfaceName::try_from(NM_ACTIVE_INTERFACE)?;
+
+    let id = String::try_from(properties.get(interface.clone(), "Id").await?)?;
+    let connection_type = String::try_from(properties.get(interface.clone(), "Type").await?)?;
+    let devices = Vec::<OwnedObjectPath>::try_from(properties.get(interface, "Devices").await?)?;
+
+    let device = match devices.first() {
+        Some(device_path) => {
+            let device = properties_proxy(conn, device_path.as_ref()).await?;
+            let interface = InterfaceName::try_from(NM_DEVICE_INTERFACE)?;
+            String::try_from(device.get(interface, "Interface").await?)?
+        }
+        None => String::new(),
+    };
+
+    Ok(ActiveConnection { id, connection_type, device })
+}
+
+fn rule_matches(rule: &NetworkRule, state: &str, connectivity: &str, connection: &ActiveConnection) -> bool {
+    let matches = |expect: &Option<Matcher>, actual: &str| {
+        expect.as_ref().is_none_or(|matcher| matcher.matches(actual))
+    };
+
+    matches(&rule.state, state)
+        && matches(&rule.connectivity, connectivity)
+        && matches(&rule.id, &connection.id)
+        && matches(&rule.connection_type, &connection.connection_type)
+        && matches(&rule.device, &connection.device)
+}
+
+/// A rule is only evaluated when one of the properties it refers to has
+/// changed, rules without conditions follow the global state.
+fn rule_triggered(rule: &NetworkRule, state_changed: bool, connectivity_changed: bool, primary_changed: bool) -> bool {
+    let refers_primary = rule.id.is_some() || rule.connection_type.is_some() || rule.device.is_some();
+    let refers_any = rule.state.is_some() || rule.connectivity.is_some() || refers_primary;
+
+    (state_changed && (rule.state.is_some() || !refers_any))
+        || (connectivity_changed && rule.connectivity.is_some())
+        || (primary_changed && refers_primary)
+}
+
+pub async fn monitor_network(rules: Vec<NetworkRule>, _sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    if rules.is_empty() {
+        return Ok(());
+    }
 
-pub async fn listen_network_prop_changes(_config: Config, _sent: Arc<Mutex<HashSet<String>>>) -> zbus::Result<()> {
     let conn = Connection::system().await?;
-    let mut stream = MessageStream::from(&conn);
+    let properties = properties_proxy(&conn, ObjectPath::try_from(NM_PATH)?).await?;
+    let interface = InterfaceName::try_from(NM_INTERFACE)?;
 
-    while let Some(result) = stream.next().await {
-        println!("{:?}", result);
-        let message = match result {
-            Ok(m) => m,
-            _     => {
-                continue;
-            }
-        };
+    // subscribing adds a match rule for PropertiesChanged on the NM object only
+    let mut changes = properties.receive_properties_changed().await?;
 
-        // Extract the member (signal name)
-        let header = message.header();
-        let member = match header.member() {
-            Some(m) => m,
-            _ => continue,
-        };
+    let mut state = u32::try_from(properties.get(interface.clone(), "State").await?)?;
+    let mut connectivity = u32::try_from(properties.get(interface.clone(), "Connectivity").await?)?;
+    let mut primary = OwnedObjectPath::try_from(properties.get(interface, "PrimaryConnection").await?)?;
 
-        if member.as_str() != "PropertiesChanged" {
+    while let Some(signal) = changes.next().await {
+        let args = signal.args()?;
+        if args.interface_name().as_str() != NM_INTERFACE {
             continue;
         }
 
-        // Deserialize the body of the message
-        let body: (String, HashMap<String, OwnedValue>, Vec<String>) =
-            message.body().deserialize()?;
+        let changed = args.changed_properties();
+        let mut state_changed = false;
+        let mut connectivity_changed = false;
+        let mut primary_changed = false;
 
-        let (interface, changed_props, _invalidated) = body;
+        if let Some(value) = changed.get("State").and_then(|v| v.downcast_ref::<u32>().ok()) {
+            state_changed = value != state;
+            state = value;
+        }
+        if let Some(value) = changed.get("Connectivity").and_then(|v| v.downcast_ref::<u32>().ok()) {
+            connectivity_changed = value != connectivity;
+            connectivity = value;
+        }
+        if let Some(value) = changed.get("PrimaryConnection").and_then(|v| v.downcast_ref::<ObjectPath>().ok()) {
+            primary_changed = value.as_str() != primary.as_str();
+            primary = value.into();
+        }
 
-        if interface != "org.freedesktop.NetworkManager" {
+        if !state_changed && !connectivity_changed && !primary_changed {
             continue;
         }
 
-        if let Some(state_val) = changed_props.get("State") {
-            let state = state_val.downcast_ref::<u32>();
-            match state {
-                Ok(20) => println!("NM: disconnected"),
-                Ok(70) => println!("NM: connected"),
-                _      => ()
-            }
+        let connection = active_connection(&conn, &primary).await.unwrap_or_default();
+        let state_name = state_name(state);
+        let connectivity_name = connectivity_name(connectivity);
+
+        for rule in rules.iter()
+            .filter(|rule| rule_triggered(rule, state_changed, connectivity_changed, primary_changed))
+            .filter(|rule| rule_matches(rule, state_name, connectivity_name, &connection))
+        {
+            let mut fields = HashMap::new();
+            fields.insert("state",        state_name.to_string());
+            fields.insert("connectivity", connectivity_name.to_string());
+            fields.insert("id",           connection.id.clone());
+            fields.insert("type",         connection.connection_type.clone());
+            fields.insert("device",       connection.device.clone());
+
+            let rule_clone = rule.clone();
+            let _ = execute_command(rule_clone.message.exec.as_ref());
+            task::spawn_blocking(move || {
+                let _ = rule_clone.message.notify(&fields);
+            })
+            .await?;
         }
     }
-    println!("2");
 
     Ok(())
 }
+
+#[derive(Debug, Default, Clone)]
+struct WifiState {
+    device: String,
+    ssid: String,
+    bssid: String,
+    signal: u32,
+    frequency: Option<u32>,
+}
+
+enum WifiBackend {
+    NetworkManager(Connection),
+    ProcFs,
+}
+
+impl WifiBackend {
+    async fn new() -> Self {
+        let nm = async {
+            let conn = Connection::system().await?;
+            wifi_states_nm(&conn).await?;
+            Ok::<_, anyhow::Error>(conn)
+        };
+
+        match nm.await {
+            Ok(conn) => {
+                info!("Using NetworkManager wireless backend");
+                Self::NetworkManager(conn)
+            }
+            Err(e) => {
+                warn!("NetworkManager is not available ({}), falling back to {}", e, PROC_WIRELESS);
+                Self::ProcFs
+            }
+        }
+    }
+
+    async fn states(&self) -> Result<Vec<WifiState>> {
+        match self {
+            Self::NetworkManager(conn) => wifi_states_nm(conn).await,
+            Self::ProcFs => Ok(parse_proc_wireless(&fs::read_to_string(PROC_WIRELESS)?)),
+        }
+    }
+}
+
+/// Reads the active access point of every wireless device, devices that
+/// are not associated are skipped.
+async fn wifi_states_nm(conn: &Connection) -> Result<Vec<WifiState>> {
+    let nm = properties_proxy(conn, ObjectPath::try_from(NM_PATH)?).await?;
+    let devices = Vec::<OwnedObjectPath>::try_from(
+        nm.get(InterfaceName::try_from(NM_INTERFACE)?, "Devices").await?
+    )?;
+
+    let mut states = Vec::new();
+    for device_path in devices {
+        // devices and access points vanish while roaming or unplugging,
+        // skip them instead of losing every other device for this tick
+        match wifi_state_nm(conn, &device_path).await {
+            Ok(Some(state)) => states.push(state),
+            Ok(None) => {}
+            Err(e) => debug!("Failed to read Wi-Fi state of {}: {}", device_path.as_str(), e),
+        }
+    }
+
+    Ok(states)
+}
+
+async fn wifi_state_nm(conn: &Connection, device_path: &OwnedObjectPath) -> Result<Option<WifiState>> {
+    let device_interface = InterfaceName::try_from(NM_DEVICE_INTERFACE)?;
+    let wireless_interface = InterfaceName::try_from(NM_WIRELESS_INTERFACE)?;
+    let ap_interface = InterfaceName::try_from(NM_ACCESS_POINT_INTERFACE)?;
+
+    let device = properties_proxy(conn, device_path.as_ref()).await?;
+    let device_type = u32::try_from(device.get(device_interface.clone(), "DeviceType").await?)?;
+    if device_type != NM_DEVICE_TYPE_WIFI {
+        return Ok(None);
+    }
+
+    let ap_path = OwnedObjectPath::try_from(
+        device.get(wireless_interface, "ActiveAccessPoint").await?
+    )?;
+    if ap_path.as_str() == "/" {
+        return Ok(None);
+    }
+
+    let ap = properties_proxy(conn, ap_path.as_ref()).await?;
+    let ssid = Vec::<u8>::try_from(ap.get(ap_interface.clone(), "Ssid").await?)?;
+
+    Ok(Some(WifiState {
+        device: String::try_from(device.get(device_interface, "Interface").await?)?,
+        ssid: String::from_utf8_lossy(&ssid).into_owned(),
+        bssid: String::try_from(ap.get(ap_interface.clone(), "HwAddress").await?)?,
+        signal: u8::try_from(ap.get(ap_interface.clone(), "Strength").await?)? as u32,
+        frequency: Some(u32::try_from(ap.get(ap_interface, "Frequency").await?)?),
+    }))
+}
+
+/// Parses link quality from `/proc/net/wireless`, which knows nothing about
+/// SSIDs and BSSIDs, so only signal rules work with this backend.
+fn parse_proc_wireless(content: &str) -> Vec<WifiState> {
+    content
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let device = columns.next()?.trim_end_matches(':').to_string();
+            let link: f32 = columns.nth(1)?.trim_end_matches('.').parse().ok()?;
+
+            Some(WifiState {
+                device,
+                // link quality is reported on a 0..70 scale by most drivers
+                signal: (link / 70.0 * 100.0).clamp(0.0, 100.0) as u32,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+pub async fn monitor_wifi(rules: Vec<WifiRule>, sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let backend = WifiBackend::new().await;
+    let mut previous: HashMap<String, WifiState> = HashMap::new();
+
+    loop {
+        let states = match backend.states().await {
+            Ok(states) => states,
+            Err(e) => {
+                warn!("Failed to read Wi-Fi state, retrying: {}", e);
+                interruptible_sleep(Duration::from_secs(10)).await;
+                continue;
+            }
+        };
+
+        for state in &states {
+            let prev = previous.get(&state.device);
+
+            for (index, rule) in rules.iter().enumerate()
+                .filter(|(_, rule)| rule.device.as_ref().is_none_or(|matcher| matcher.matches(&state.device)))
+            {
+                let should_notify = match rule.event.as_str() {
+                    "signal" => {
+                        let key = format!("wifi-{}-{}-{}", index, state.device, rule.level);
+                        let mut sent_guard = sent.lock().unwrap();
+
+                        if (state.signal as f32) < rule.level {
+                            sent_guard.insert(key)
+                        } else {
+                            sent_guard.remove(&key);
+                            false
+                        }
+                    }
+                    "roam" => prev.is_some_and(|prev| {
+                        prev.ssid == state.ssid && !prev.bssid.is_empty() && prev.bssid != state.bssid
+                    }),
+                    "ssid" => prev.is_some_and(|prev| {
+                        !state.ssid.is_empty() && prev.ssid != state.ssid
+                    }),
+                    _ => false,
+                };
+
+                if should_notify {
+                    let mut fields = HashMap::new();
+                    fields.insert("level",          rule.level.to_string());
+                    fields.insert("device",         state.device.clone());
+                    fields.insert("ssid",           state.ssid.clone());
+                    fields.insert("bssid",          state.bssid.clone());
+                    fields.insert("signal",         state.signal.to_string());
+                    fields.insert("frequency",      state.frequency.map(|f| f.to_string()).unwrap_or_default());
+                    fields.insert("previous_ssid",  prev.map(|p| p.ssid.clone()).unwrap_or_default());
+                    fields.insert("previous_bssid", prev.map(|p| p.bssid.clone()).unwrap_or_default());
+
+                    let rule_clone = rule.clone();
+                    let _ = execute_command(rule_clone.message.exec.as_ref());
+                    task::spawn_blocking(move || {
+                        let _ = rule_clone.message.notify(&fields);
+                    })
+                    .await?;
+                }
+            }
+        }
+
+        // keep the last known access point while disconnected, so that
+        // joining another network afterwards is still reported
+        for state in states {
+            previous.insert(state.device.clone(), state);
+        }
+
+        interruptible_sleep(Duration::from_secs(10)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proc_wireless() {
+        let content = "\
+Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE
+ face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22
+wlp3s0: 0000   54.  -56.  -256        0      0      0      0     10        0
+ wlan1: 0000   80.  -20.  -256        0      0      0      0      0        0
+";
+
+        let states = parse_proc_wireless(content);
+        assert_eq!(states.len(), 2);
+        assert_eq!(states[0].device, "wlp3s0");
+        assert_eq!(states[0].signal, 77);
+        assert!(states[0].ssid.is_empty() && states[0].frequency.is_none());
+        // drivers reporting above the 0..70 scale are clamped
+        assert_eq!(states[1].device, "wlan1");
+        assert_eq!(states[1].signal, 100);
+    }
+
+    #[test]
+    fn proc_wireless_without_devices() {
+        let content = "\
+Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE
+ face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22
+";
+        assert!(parse_proc_wireless(content).is_empty());
+    }
+}
@@ -0,0 +1,280 @@
+use anyhow::Result;
+use humansize::{format_size, DECIMAL};
+use log::warn;
+use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    process::Stdio,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, BufReader},
+    process::Command,
+    sync::mpsc,
+    task,
+};
+
+use crate::{
+    config::OomRule,
+    kmsg::{Kmsg, KMSG_PATH},
+    session::interruptible_sleep,
+    utils::execute_command,
+};
+
+const VMSTAT_PATH: &str = "/proc/vmstat";
+
+pub const OOM_SOURCES: &[&str] = &["kernel", "oomd"];
+
+static KERNEL_KILL_REGEX: OnceLock<Regex> = OnceLock::new();
+static OOMD_KILL_REGEX: OnceLock<Regex> = OnceLock::new();
+
+fn get_kernel_kill_regex() -> &'static Regex {
+    KERNEL_KILL_REGEX.get_or_init(|| {
+        Regex::new(r"Killed process (\d+) \((.*?)\).*?anon-rss:(\d+)kB, file-rss:(\d+)kB, shmem-rss:(\d+)kB(?:, UID:(\d+))?")
+            .expect("Failed to compile kernel OOM regex")
+    })
+}
+
+fn get_oomd_kill_regex() -> &'static Regex {
+    OOMD_KILL_REGEX.get_or_init(|| {
+        Regex::new(r"Killed (\S+) due to (.+)")
+            .expect("Failed to compile systemd-oomd regex")
+    })
+}
+
+#[derive(Debug, Default, Clone)]
+struct OomEvent {
+    source: &'static str,
+    name: String,
+    pid: String,
+    rss: Option<u64>,
+    uid: String,
+    cgroup: String,
+    reason: String,
+}
+
+fn parse_kernel_record(message: &str) -> Option<OomEvent> {
+    let caps = get_kernel_kill_regex().captures(message)?;
+    let kb = |i: usize| caps.get(i).and_then(|m| m.as_str().parse::<u64>().ok()).unwrap_or(0);
+
+    Some(OomEvent {
+        source: "kernel",
+        name: caps[2].to_string(),
+        pid: caps[1].to_string(),
+        rss: Some((kb(3) + kb(4) + kb(5)) * 1024),
+        uid: caps.get(6).map(|m| m.as_str().to_string()).unwrap_or_default(),
+        reason: "out of memory".to_string(),
+        ..Default::default()
+    })
+}
+
+fn parse_oomd_line(line: &str) -> Option<OomEvent> {
+    let caps = get_oomd_kill_regex().captures(line)?;
+    let cgroup = caps[1].to_string();
+
+    Some(OomEvent {
+        source: "oomd",
+        // the unit name is the most descriptive thing oomd reports about the victim
+        name: cgroup.rsplit('/').next().unwrap_or_default().to_string(),
+        cgroup,
+        reason: caps[2].to_string(),
+        ..Default::default()
+    })
+}
+
+async fn read_oom_kill_count() -> Option<u64> {
+    fs::read_to_string(VMSTAT_PATH)
+        .await
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+async fn watch_kmsg(mut kmsg: Kmsg, events: mpsc::Sender<OomEvent>) -> Result<()> {
+    while let Some(record) = kmsg.next_record().await? {
+        if let Some(event) = parse_kernel_record(&record.message) {
+            events.send(event).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Without access to the kernel log only the number of kills is known.
+async fn watch_vmstat(events: mpsc::Sender<OomEvent>) -> Result<()> {
+    let mut previous = read_oom_kill_count().await;
+
+    loop {
+        interruptible_sleep(Duration::from_secs(10)).await;
+
+        let current = read_oom_kill_count().await;
+        if let (Some(previous), Some(current)) = (previous, current) {
+            for _ in previous..current {
+                events.send(OomEvent {
+                    source: "kernel",
+                    reason: "out of memory".to_string(),
+                    ..Default::default()
+                }).await?;
+            }
+        }
+        previous = current;
+    }
+}
+
+async fn watch_oomd(events: mpsc::Sender<OomEvent>) -> Result<()> {
+    let mut child = Command::new("journalctl")
+        .args(["--follow", "--lines=0", "--output=cat", "--unit=systemd-oomd.service"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let Some(stdout) = child.stdout.take() else {
+        return Ok(());
+    };
+
+    let mut lines = BufReader::new(stdout).lines();
+    while let Some(line) = lines.next_line().await? {
+        if let Some(event) = parse_oomd_line(&line) {
+            events.send(event).await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn monitor_oom(rules: Vec<OomRule>, _sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let (sender, mut receiver) = mpsc::channel(16);
+
+    if rules.iter().any(|rule| rule.source.as_deref().is_none_or(|source| source == "kernel")) {
+        let sender = sender.clone();
+        match Kmsg::open().await {
+            Ok(kmsg) => {
+                task::spawn(async move {
+                    if let Err(e) = watch_kmsg(kmsg, sender).await {
+                        warn!("Failed to read {}: {}", KMSG_PATH, e);
+                    }
+                });
+            }
+            Err(e) => {
+                warn!("Cannot open {} ({}), falling back to the oom_kill counter", KMSG_PATH, e);
+                task::spawn(async move {
+                    if let Err(e) = watch_vmstat(sender).await {
+                        warn!("Failed to read {}: {}", VMSTAT_PATH, e);
+                    }
+                });
+            }
+        }
+    }
+
+    if rules.iter().any(|rule| rule.source.as_deref().is_none_or(|source| source == "oomd")) {
+        let sender = sender.clone();
+        task::spawn(async move {
+            if let Err(e) = watch_oomd(sender).await {
+                warn!("Failed to follow systemd-oomd journal: {}", e);
+            }
+        });
+    }
+
+    drop(sender);
+
+    while let Some(event) = receiver.recv().await {
+        for rule in rules.iter()
+            .filter(|rule| rule.source.as_deref().is_none_or(|source| source == event.source))
+            .filter(|rule| rule.name.as_ref().is_none_or(|matcher| matcher.matches(&event.name)))
+        {
+            let mut fields = HashMap::new();
+            fields.insert("source",    event.source.to_string());
+            fields.insert("name",      event.name.clone());
+            fields.insert("pid",       event.pid.clone());
+            fields.insert("rss_bytes", event.rss.map(|rss| rss.to_string()).unwrap_or_default());
+            fields.insert("rss",       event.rss.map(|rss| format_size(rss, DECIMAL)).unwrap_or_default());
+            fields.insert("uid",       event.uid.clone());
+            fields.insert("cgroup",    event.cgroup.clone());
+            fields.insert("reason",    event.reason.clone());
+
+            let rule_clone = rule.clone();
+            let _ = execute_command(rule_clone.message.exec.as_ref());
+            task::spawn_blocking(move || {
+                let _ = rule_clone.message.notify(&fields);
+            })
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernel_oom_kill() {
+        let event = parse_kernel_record(
+            "Out of memory: Killed process 12345 (stress-ng) total-vm:8242612kB, anon-rss:7862320kB, \
+             file-rss:4kB, shmem-rss:8kB, UID:1000 pgtables:15420kB oom_score_adj:1000"
+        ).unwrap();
+
+        assert_eq!(event.source, "kernel");
+        assert_eq!(event.pid, "12345");
+        assert_eq!(event.name, "stress-ng");
+        assert_eq!(event.rss, Some((7862320 + 4 + 8) * 1024));
+        assert_eq!(event.uid, "1000");
+    }
+
+    #[test]
+    fn memory_cgroup_oom_kill() {
+        let event = parse_kernel_record(
+            "Memory cgroup out of memory: Killed process 4321 (Web Content) total-vm:5000000kB, \
+             anon-rss:2097152kB, file-rss:1024kB, shmem-rss:0kB, UID:0 pgtables:4500kB oom_score_adj:0"
+        ).unwrap();
+
+        assert_eq!(event.pid, "4321");
+        assert_eq!(event.name, "Web Content");
+        assert_eq!(event.rss, Some((2097152 + 1024) * 1024));
+        assert_eq!(event.uid, "0");
+    }
+
+    #[test]
+    fn kernel_oom_kill_without_uid() {
+        // kernels before 5.1 don't report the UID
+        let event = parse_kernel_record(
+            "Killed process 2592 (mysqld) total-vm:2190152kB, anon-rss:1062500kB, file-rss:0kB, shmem-rss:0kB"
+        ).unwrap();
+
+        assert_eq!(event.name, "mysqld");
+        assert_eq!(event.uid, "");
+    }
+
+    #[test]
+    fn unrelated_kernel_records() {
+        assert!(parse_kernel_record("stress-ng invoked oom-killer: gfp_mask=0x140dca(GFP_HIGHUSER_MOVABLE|__GFP_COMP|__GFP_ZERO), order=0, oom_score_adj=1000").is_none());
+        assert!(parse_kernel_record("oom-kill:constraint=CONSTRAINT_NONE,nodemask=(null),cpuset=/,mems_allowed=0,global_oom,task_memcg=/user.slice,task=stress-ng,pid=12345,uid=1000").is_none());
+        assert!(parse_kernel_record("oom_reaper: reaped process 12345 (stress-ng), now anon-rss:0kB, file-rss:0kB, shmem-rss:0kB").is_none());
+    }
+
+    #[test]
+    fn oomd_kill() {
+        let event = parse_oomd_line(
+            "Killed /user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox-1234.scope \
+             due to memory pressure for /user.slice/user-1000.slice/user@1000.service being 71.23% > 50.00% \
+             for > 20s with reclaim activity"
+        ).unwrap();
+
+        assert_eq!(event.source, "oomd");
+        assert_eq!(event.name, "app-firefox-1234.scope");
+        assert_eq!(event.cgroup, "/user.slice/user-1000.slice/user@1000.service/app.slice/app-firefox-1234.scope");
+        assert!(event.reason.starts_with("memory pressure for /user.slice/user-1000.slice/user@1000.service"));
+        assert!(parse_oomd_line("Considered 3 cgroups for killing, top candidates were:").is_none());
+    }
+}
@@ -0,0 +1,344 @@
+use anyhow::{anyhow, bail, Result};
+use futures_util::future::join_all;
+use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+use tokio::{net::TcpStream, process::Command, task, time::timeout};
+
+use crate::{
+    config::ProbeRule,
+    session::{interruptible_sleep, ResumeWatch},
+    utils::execute_command,
+};
+
+pub const PROBE_KINDS: &[&str] = &["icmp", "tcp", "http"];
+pub const PROBE_EVENTS: &[&str] = &["failure", "recovery", "latency"];
+
+static PING_TIME_REGEX: OnceLock<Regex> = OnceLock::new();
+
+fn get_ping_time_regex() -> &'static Regex {
+    PING_TIME_REGEX.get_or_init(|| {
+        Regex::new(r"time[=<]([0-9.]+) ms")
+            .expect("Failed to compile ping time regex")
+    })
+}
+
+#[derive(Debug, Default)]
+struct ProbeResult {
+    latency: Option<Duration>,
+    status: String,
+    error: Option<String>,
+}
+
+/// Everything that changes how a target is checked, rules sharing the same
+/// spec are evaluated against a single probe.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ProbeSpec {
+    kind: String,
+    target: String,
+    expect_status: Option<u16>,
+    expect_body: Option<String>,
+    probe_timeout: u64,
+}
+
+impl From<&ProbeRule> for ProbeSpec {
+    fn from(rule: &ProbeRule) -> Self {
+        Self {
+            kind: rule.kind.clone(),
+            target: rule.target.clone(),
+            expect_status: rule.expect_status,
+            expect_body: rule.expect_body.clone(),
+            probe_timeout: rule.probe_timeout,
+        }
+    }
+}
+
+async fn probe_icmp(spec: &ProbeSpec) -> Result<ProbeResult> {
+    let output = Command::new("ping")
+        .args(["-n", "-c", "1", "-W", &spec.probe_timeout.to_string(), &spec.target])
+        .output()
+        .await?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.trim();
+        bail!("{}", if message.is_empty() { "no reply" } else { message });
+    }
+
+    let latency = get_ping_time_regex()
+        .captures(&stdout)
+        .and_then(|caps| caps[1].parse::<f64>().ok())
+        .map(|ms| Duration::from_secs_f64(ms / 1000.0));
+
+    Ok(ProbeResult { latency, ..Default::default() })
+}
+
+async fn probe_tcp(spec: &ProbeSpec) -> Result<ProbeResult> {
+    let started = Instant::now();
+    timeout(Duration::from_secs(spec.probe_timeout), TcpStream::connect(&spec.target))
+        .await
+        .map_err(|_| anyhow!("connection timed out"))??;
+
+    Ok(ProbeResult { latency: Some(started.elapsed()), ..Default::default() })
+}
+
+async fn probe_http(spec: &ProbeSpec, client: &reqwest::Client, body_regex: Option<&Regex>) -> Result<ProbeResult> {
+    let started = Instant::now();
+    let response = client
+        .get(&spec.target)
+        .timeout(Duration::from_secs(spec.probe_timeout))
+        .send()
+        .await?;
+    let status = response.status();
+    let body = response.text().await?;
+    let latency = Some(started.elapsed());
+
+    let status_ok = match spec.expect_status {
+        Some(expected) => status.as_u16() == expected,
+        None => status.is_success(),
+    };
+    let error = if !status_ok {
+        Some(format!("unexpected status {}", status))
+    } else if body_regex.is_some_and(|regex| !regex.is_match(&body)) {
+        Some("response body does not match".to_string())
+    } else {
+        None
+    };
+
+    Ok(ProbeResult { latency, status: status.as_u16().to_string(), error })
+}
+
+async fn run_probe(spec: &ProbeSpec, client: &reqwest::Client, body_regex: Option<&Regex>) -> ProbeResult {
+    let result = match spec.kind.as_str() {
+        "icmp" => probe_icmp(spec).await,
+        "tcp"  => probe_tcp(spec).await,
+        "http" => probe_http(spec, client, body_regex).await,
+        kind   => Err(anyhow!("unknown probe kind {}", kind)),
+    };
+
+    result.unwrap_or_else(|e| ProbeResult {
+        error: Some(format!("{:#}", e)),
+        ..Default::default()
+    })
+}
+
+/// Decides whether `rule` fires for a probe result, `failing` is the outcome
+/// of the previous probe.
+fn should_notify(index: usize, rule: &ProbeRule, result: &ProbeResult, failing: Option<bool>, sent: &Mutex<HashSet<String>>) -> bool {
+    let failed = result.error.is_some();
+
+    match rule.event.as_str() {
+        "failure" => {
+            let key = format!("probe-failure-{}", index);
+            let mut sent_guard = sent.lock().unwrap();
+
+            if failed {
+                sent_guard.insert(key)
+            } else {
+                sent_guard.remove(&key);
+                false
+            }
+        }
+        "recovery" => failing == Some(true) && !failed,
+        "latency" => {
+            let key = format!("probe-latency-{}", index);
+            let mut sent_guard = sent.lock().unwrap();
+
+            if result.latency.is_some_and(|latency| latency.as_millis() as u64 > rule.latency) {
+                sent_guard.insert(key)
+            } else {
+                sent_guard.remove(&key);
+                false
+            }
+        }
+        _ => false,
+    }
+}
+
+async fn monitor_probe(spec: ProbeSpec, rules: Vec<(usize, ProbeRule)>, sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    let client = reqwest::Client::new();
+    let body_regex = spec.expect_body.as_deref().map(Regex::new).transpose()?;
+    let interval = rules.iter().map(|(_, rule)| rule.interval).min().unwrap_or(60);
+    let mut failing: Option<bool> = None;
+    let mut resume = ResumeWatch::new();
+
+    loop {
+        // the network is usually down while suspended, don't report that as a recovery
+        if resume.resumed() {
+            failing = None;
+        }
+
+        let result = run_probe(&spec, &client, body_regex.as_ref()).await;
+        let failed = result.error.is_some();
+        let latency_ms = result.latency.map(|l| l.as_millis() as u64);
+
+        for (index, rule) in &rules {
+            if should_notify(*index, rule, &result, failing, &sent) {
+                let mut fields = HashMap::new();
+                fields.insert("kind",       spec.kind.clone());
+                fields.insert("target",     spec.target.clone());
+                fields.insert("event",      rule.event.clone());
+                fields.insert("latency_ms", latency_ms.map(|ms| ms.to_string()).unwrap_or_default());
+                fields.insert("status",     result.status.clone());
+                fields.insert("error",      result.error.clone().unwrap_or_default());
+
+                let rule_clone = rule.clone();
+                let _ = execute_command(rule_clone.message.exec.as_ref());
+                task::spawn_blocking(move || {
+                    let _ = rule_clone.message.notify(&fields);
+                })
+                .await?;
+            }
+        }
+
+        failing = Some(failed);
+        interruptible_sleep(Duration::from_secs(interval)).await;
+    }
+}
+
+pub async fn monitor_probes(rules: Vec<ProbeRule>, sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    let mut groups: HashMap<ProbeSpec, Vec<(usize, ProbeRule)>> = HashMap::new();
+    for (index, rule) in rules.into_iter().enumerate() {
+        groups.entry(ProbeSpec::from(&rule)).or_default().push((index, rule));
+    }
+
+    let probes = groups
+        .into_iter()
+        .map(|(spec, rules)| monitor_probe(spec, rules, sent.clone()));
+
+    for result in join_all(probes).await {
+        result?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    fn rule(kind: &str, event: &str, target: &str) -> ProbeRule {
+        ProbeRule {
+            kind: kind.to_string(),
+            event: event.to_string(),
+            target: target.to_string(),
+            probe_timeout: 2,
+            ..Default::default()
+        }
+    }
+
+    async fn probe(rule: &ProbeRule) -> ProbeResult {
+        let spec = ProbeSpec::from(rule);
+        let body_regex = spec.expect_body.as_deref().map(|body| Regex::new(body).unwrap());
+        run_probe(&spec, &reqwest::Client::new(), body_regex.as_ref()).await
+    }
+
+    /// Serves a single canned HTTP response per connection after `delay`.
+    async fn http_server(status: &'static str, body: &'static str, delay: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                tokio::time::sleep(delay).await;
+                let response = format!(
+                    "HTTP/1.1 {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status, body.len(), body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        format!("http://{}/health", addr)
+    }
+
+    #[tokio::test]
+    async fn tcp_failure_and_recovery() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+
+        let failure = rule("tcp", "failure", &target);
+        let recovery = rule("tcp", "recovery", &target);
+        let sent = Mutex::new(HashSet::new());
+
+        let result = probe(&failure).await;
+        assert!(result.error.is_none() && result.latency.is_some());
+        assert!(!should_notify(0, &failure, &result, None, &sent));
+        assert!(!should_notify(1, &recovery, &result, None, &sent));
+
+        drop(listener);
+        let result = probe(&failure).await;
+        assert!(result.error.is_some());
+        assert!(should_notify(0, &failure, &result, Some(false), &sent));
+        assert!(!should_notify(1, &recovery, &result, Some(false), &sent));
+        // a failure is reported once until the target recovers
+        assert!(!should_notify(0, &failure, &result, Some(true), &sent));
+
+        let _listener = TcpListener::bind(&target).await.unwrap();
+        let result = probe(&failure).await;
+        assert!(result.error.is_none());
+        assert!(!should_notify(0, &failure, &result, Some(true), &sent));
+        assert!(should_notify(1, &recovery, &result, Some(true), &sent));
+        assert!(sent.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn http_status_and_body() {
+        let target = http_server("200 OK", "healthy", Duration::ZERO).await;
+        let mut check = rule("http", "failure", &target);
+        let result = probe(&check).await;
+        assert_eq!(result.status, "200");
+        assert!(result.error.is_none());
+
+        check.expect_body = Some("^ok$".to_string());
+        assert_eq!(probe(&check).await.error.as_deref(), Some("response body does not match"));
+
+        let target = http_server("503 Service Unavailable", "", Duration::ZERO).await;
+        let check = rule("http", "failure", &target);
+        let result = probe(&check).await;
+        assert_eq!(result.status, "503");
+        assert!(result.error.is_some_and(|e| e.starts_with("unexpected status")));
+
+        let mut check = rule("http", "failure", &target);
+        check.expect_status = Some(503);
+        assert!(probe(&check).await.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn http_latency() {
+        let target = http_server("200 OK", "", Duration::from_millis(200)).await;
+        let mut latency = rule("http", "latency", &target);
+        latency.latency = 100;
+        let sent = Mutex::new(HashSet::new());
+
+        let result = probe(&latency).await;
+        assert!(result.latency.is_some_and(|l| l >= Duration::from_millis(200)));
+        assert!(should_notify(0, &latency, &result, Some(false), &sent));
+        assert!(!should_notify(0, &latency, &result, Some(false), &sent));
+
+        latency.latency = 1000;
+        assert!(!should_notify(0, &latency, &result, Some(false), &sent));
+        assert!(sent.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn http_timeout() {
+        let target = http_server("200 OK", "", Duration::from_secs(5)).await;
+        let mut check = rule("http", "failure", &target);
+        check.probe_timeout = 1;
+
+        let result = probe(&check).await;
+        assert!(result.error.is_some());
+        assert!(result.latency.is_none());
+    }
+}
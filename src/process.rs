@@ -0,0 +1,283 @@
+use anyhow::Result;
+use humansize::{format_size, DECIMAL};
+use regex::Regex;
+use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}, time::Duration};
+use sysinfo::{Pid, Process, ProcessRefreshKind, ProcessesToUpdate, System, Uid, UpdateKind, Users};
+use tokio::task;
+
+use crate::{
+    config::ProcessRule,
+    message::Message,
+    session::interruptible_sleep,
+    utils::execute_command,
+};
+
+pub const PROCESS_EVENTS: &[&str] = &["appeared", "exited", "not_running", "cpu", "rss"];
+
+#[derive(Debug, Clone)]
+struct ProcessInfo {
+    pid: Pid,
+    name: String,
+    cmdline: String,
+    exe: String,
+    user: String,
+    cpu: f32,
+    rss: u64,
+}
+
+impl ProcessInfo {
+    fn new(pid: Pid, process: &Process, users: &Users) -> Self {
+        Self {
+            pid,
+            name: process.name().to_string_lossy().into_owned(),
+            cmdline: process.cmd()
+                .iter()
+                .map(|arg| arg.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" "),
+            exe: process.exe().map(|exe| exe.display().to_string()).unwrap_or_default(),
+            user: process.user_id()
+                .and_then(|uid| users.get_user_by_id(uid))
+                .map(|user| user.name().to_string())
+                .unwrap_or_default(),
+            cpu: process.cpu_usage(),
+            rss: process.memory(),
+        }
+    }
+
+    fn fields(&self) -> HashMap<&'static str, String> {
+        let mut fields = HashMap::new();
+        fields.insert("pid",       self.pid.to_string());
+        fields.insert("name",      self.name.clone());
+        fields.insert("cmdline",   self.cmdline.clone());
+        fields.insert("exe",       self.exe.clone());
+        fields.insert("user",      self.user.clone());
+        fields.insert("cpu",       format!("{:.1}", self.cpu));
+        fields.insert("rss_bytes", self.rss.to_string());
+        fields.insert("rss",       format_size(self.rss, DECIMAL));
+        fields
+    }
+}
+
+/// Refreshes what `top_fields` needs, cpu usage is computed between two calls.
+pub fn refresh_usage(sys: &mut System) {
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing().with_cpu().with_memory(),
+    );
+}
+
+/// Number of top processes a rule needs, none unless its message refers to
+/// `top` or a `topN_*` field, so that the process table is only scanned when
+/// something shows it.
+pub fn top_count(top: usize, message: &Message) -> usize {
+    let shows_top = message.placeholders().into_iter().any(|field| {
+        field.strip_prefix("top").is_some_and(|rest| rest.is_empty() || rest.starts_with(|c: char| c.is_ascii_digit()))
+    });
+    if shows_top { top } else { 0 }
+}
+
+/// Builds `top1_name`, `top1_pid`, `top1_cpu`, `top1_rss`, ... fields for the
+/// `count` processes using the most cpu (or memory), plus a preformatted `top` list.
+pub fn top_fields(sys: &System, count: usize, by_cpu: bool) -> HashMap<String, String> {
+    let mut processes: Vec<(&Pid, &Process)> = sys.processes()
+        .iter()
+        .filter(|(_, process)| process.thread_kind().is_none())
+        .collect();
+
+    if by_cpu {
+        processes.sort_by(|(_, a), (_, b)| b.cpu_usage().total_cmp(&a.cpu_usage()));
+    } else {
+        processes.sort_by_key(|(_, process)| std::cmp::Reverse(process.memory()));
+    }
+
+    let mut fields = HashMap::new();
+    let mut lines = Vec::new();
+
+    for (i, (pid, process)) in processes.into_iter().take(count).enumerate() {
+        let name = process.name().to_string_lossy().into_owned();
+        let cpu = format!("{:.1}", process.cpu_usage());
+        let rss = format_size(process.memory(), DECIMAL);
+
+        lines.push(if by_cpu {
+            format!("{} ({}%)", name, cpu)
+        } else {
+            format!("{} ({})", name, rss)
+        });
+
+        let prefix = format!("top{}", i + 1);
+        fields.insert(format!("{}_name", prefix),      name);
+        fields.insert(format!("{}_pid", prefix),       pid.to_string());
+        fields.insert(format!("{}_cpu", prefix),       cpu);
+        fields.insert(format!("{}_rss", prefix),       rss);
+        fields.insert(format!("{}_rss_bytes", prefix), process.memory().to_string());
+    }
+
+    fields.insert("top".to_string(), lines.join("\n"));
+    fields
+}
+
+fn rule_matches(rule: &ProcessRule, cmdline_regex: Option<&Regex>, info: &ProcessInfo) -> bool {
+    rule.name.as_ref().is_none_or(|matcher| matcher.matches(&info.name))
+        && rule.exe.as_ref().is_none_or(|matcher| matcher.matches(&info.exe))
+        && rule.user.as_ref().is_none_or(|matcher| matcher.matches(&info.user))
+        && cmdline_regex.is_none_or(|regex| regex.is_match(&info.cmdline))
+}
+
+async fn notify(rule: &ProcessRule, mut fields: HashMap<&'static str, String>) -> Result<()> {
+    fields.insert("event", rule.event.clone());
+
+    let rule_clone = rule.clone();
+    let _ = execute_command(rule_clone.message.exec.as_ref());
+    task::spawn_blocking(move || {
+        let _ = rule_clone.message.notify(&fields);
+    })
+    .await?;
+
+    Ok(())
+}
+
+pub async fn monitor_processes(rules: Vec<ProcessRule>, sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let cmdline_regexes = rules
+        .iter()
+        .map(|rule| rule.cmdline.as_deref().map(Regex::new).transpose())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // kept across iterations, cpu usage is computed between two refreshes
+    let mut sys = System::new();
+    let mut users = Users::new_with_refreshed_list();
+    // uids without a passwd entry, e.g. of containers, that were looked up once
+    let mut unresolved: HashSet<Uid> = HashSet::new();
+    let refresh_kind = ProcessRefreshKind::nothing()
+        .with_cpu()
+        .with_memory()
+        .with_cmd(UpdateKind::OnlyIfNotSet)
+        .with_exe(UpdateKind::OnlyIfNotSet)
+        .with_user(UpdateKind::OnlyIfNotSet);
+
+    let mut matched: Vec<HashMap<Pid, ProcessInfo>> = vec![HashMap::new(); rules.len()];
+    let mut first_run = true;
+
+    loop {
+        sys.refresh_processes_specifics(ProcessesToUpdate::All, true, refresh_kind);
+
+        let new_user = sys.processes()
+            .values()
+            .filter_map(|process| process.user_id())
+            .any(|uid| users.get_user_by_id(uid).is_none() && !unresolved.contains(uid));
+        if new_user {
+            users.refresh();
+            unresolved = sys.processes()
+                .values()
+                .filter_map(|process| process.user_id())
+                .filter(|uid| users.get_user_by_id(uid).is_none())
+                .cloned()
+                .collect();
+        }
+
+        let processes: Vec<ProcessInfo> = sys.processes()
+            .iter()
+            .filter(|(_, process)| process.thread_kind().is_none())
+            .map(|(pid, process)| ProcessInfo::new(*pid, process, &users))
+            .collect();
+
+        for (index, rule) in rules.iter().enumerate() {
+            let current: HashMap<Pid, ProcessInfo> = processes
+                .iter()
+                .filter(|info| rule_matches(rule, cmdline_regexes[index].as_ref(), info))
+                .map(|info| (info.pid, info.clone()))
+                .collect();
+            let previous = std::mem::replace(&mut matched[index], current);
+            let current = &matched[index];
+
+            match rule.event.as_str() {
+                // processes running at startup are not reported as appeared
+                "appeared" if !first_run => {
+                    for info in current.values().filter(|info| !previous.contains_key(&info.pid)) {
+                        notify(rule, info.fields()).await?;
+                    }
+                }
+                "exited" => {
+                    for info in previous.values().filter(|info| !current.contains_key(&info.pid)) {
+                        notify(rule, info.fields()).await?;
+                    }
+                }
+                "not_running" => {
+                    let should_notify = {
+                        let key = format!("process-not-running-{}", index);
+                        let mut sent_guard = sent.lock().unwrap();
+
+                        if current.is_empty() {
+                            sent_guard.insert(key)
+                        } else {
+                            sent_guard.remove(&key);
+                            false
+                        }
+                    };
+
+                    if should_notify {
+                        let mut fields = HashMap::new();
+                        fields.insert("name",    rule.name.as_ref().map(ToString::to_string).unwrap_or_default());
+                        fields.insert("cmdline", rule.cmdline.clone().unwrap_or_default());
+                        fields.insert("exe",     rule.exe.as_ref().map(ToString::to_string).unwrap_or_default());
+                        fields.insert("user",    rule.user.as_ref().map(ToString::to_string).unwrap_or_default());
+                        notify(rule, fields).await?;
+                    }
+                }
+                "cpu" | "rss" => {
+                    for info in previous.values().filter(|info| !current.contains_key(&info.pid)) {
+                        sent.lock().unwrap().remove(&format!("process-{}-{}-{}", rule.event, index, info.pid));
+                    }
+
+                    for info in current.values() {
+                        let exceeded = match rule.event.as_str() {
+                            "cpu" => info.cpu >= rule.level,
+                            _     => rule.rss.is_some_and(|rss| info.rss >= rss),
+                        };
+
+                        let should_notify = {
+                            let key = format!("process-{}-{}-{}", rule.event, index, info.pid);
+                            let mut sent_guard = sent.lock().unwrap();
+
+                            if exceeded {
+                                sent_guard.insert(key)
+                            } else {
+                                sent_guard.remove(&key);
+                                false
+                            }
+                        };
+
+                        if should_notify {
+                            notify(rule, info.fields()).await?;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        first_run = false;
+        interruptible_sleep(Duration::from_secs(10)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_count_follows_placeholders() {
+        let message = |body: &str| Message { body: Some(body.to_string()), ..Default::default() };
+
+        assert_eq!(top_count(3, &message("CPU usage is at {used_percent}%")), 0);
+        assert_eq!(top_count(3, &message("{top}")), 3);
+        assert_eq!(top_count(3, &message("{top1_name} uses {top1_cpu}%")), 3);
+        assert_eq!(top_count(3, &message("{topology}")), 0);
+        assert_eq!(top_count(0, &message("{top}")), 0);
+    }
+}
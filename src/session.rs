@@ -0,0 +1,309 @@
+use anyhow::Result;
+use futures_util::StreamExt;
+use log::warn;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::{sync::Notify, task, time::{interval, sleep, Interval, MissedTickBehavior}};
+use zbus::{fdo::PropertiesProxy, zvariant::{OwnedFd, OwnedObjectPath}, Connection, Proxy};
+use zbus_names::InterfaceName;
+
+use crate::{
+    battery::BatteryBackend,
+    config::{BatteryBackendKind, SessionRule},
+    utils::execute_command,
+};
+
+const LOGIN_DESTINATION: &str = "org.freedesktop.login1";
+const LOGIN_PATH: &str = "/org/freedesktop/login1";
+const LOGIN_SESSION_AUTO_PATH: &str = "/org/freedesktop/login1/session/auto";
+const MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+const LID_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+pub const SESSION_EVENTS: &[&str] = &[
+    "sleep",
+    "resume",
+    "shutdown",
+    "lock",
+    "unlock",
+    "lid_closed",
+    "lid_opened",
+];
+
+static RESUMED: Notify = Notify::const_new();
+static RESUME_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Lets a monitor find out whether the system resumed since it last looked,
+/// so that it can reset conditions measured across the suspend.
+#[derive(Debug)]
+pub struct ResumeWatch {
+    seen: u64,
+}
+
+impl ResumeWatch {
+    pub fn new() -> Self {
+        Self { seen: RESUME_COUNT.load(Ordering::Relaxed) }
+    }
+
+    pub fn resumed(&mut self) -> bool {
+        let count = RESUME_COUNT.load(Ordering::Relaxed);
+        let resumed = count != self.seen;
+        self.seen = count;
+        resumed
+    }
+}
+
+/// Sleeps for `duration`, waking up early when the system resumes from
+/// suspend so that level monitors re-sample right away.
+pub async fn interruptible_sleep(duration: Duration) {
+    tokio::select! {
+        _ = sleep(duration) => {}
+        _ = RESUMED.notified() => {}
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60) {
+        (0, 0)       => format!("{}s", secs),
+        (0, minutes) => format!("{}m", minutes),
+        (hours, 0)   => format!("{}h", hours),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
+
+/// Proxy of the session alertify runs in, `auto` resolves to the caller's
+/// session or the user's display session, but signals are emitted on the
+/// session's real path.
+async fn session_proxy(conn: &Connection, manager: &Proxy<'_>) -> Result<Proxy<'static>> {
+    let auto = PropertiesProxy::builder(conn)
+        .destination(LOGIN_DESTINATION)?
+        .path(LOGIN_SESSION_AUTO_PATH)?
+        .build()
+        .await?;
+    let id = String::try_from(auto.get(InterfaceName::try_from(SESSION_INTERFACE)?, "Id").await?)?;
+
+    let reply = manager.call_method("GetSession", &(id.as_str(),)).await?;
+    let path = reply.body().deserialize::<OwnedObjectPath>()?;
+
+    Ok(Proxy::new(conn, LOGIN_DESTINATION, path, SESSION_INTERFACE).await?)
+}
+
+/// Polls logind's `LidClosed`, the property doesn't emit change signals.
+struct LidWatch {
+    properties: PropertiesProxy<'static>,
+    interface: InterfaceName<'static>,
+    timer: Interval,
+    closed: Option<bool>,
+}
+
+impl LidWatch {
+    async fn new(conn: &Connection) -> Result<Self> {
+        let properties = PropertiesProxy::builder(conn)
+            .destination(LOGIN_DESTINATION)?
+            .path(LOGIN_PATH)?
+            .build()
+            .await?;
+        let mut timer = interval(LID_POLL_INTERVAL);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        Ok(Self {
+            properties,
+            interface: InterfaceName::try_from(MANAGER_INTERFACE)?,
+            timer,
+            closed: None,
+        })
+    }
+
+    /// Waits for the next poll, returns the new state when the lid was
+    /// opened or closed since the previous one.
+    async fn changed(&mut self) -> Result<Option<bool>> {
+        self.timer.tick().await;
+
+        let closed = bool::try_from(self.properties.get(self.interface.clone(), "LidClosed").await?)?;
+        let previous = self.closed.replace(closed);
+
+        Ok(previous.filter(|&previous| previous != closed).map(|_| closed))
+    }
+}
+
+/// Takes a delay lock, so that logind waits for the state to be captured
+/// before suspending instead of freezing alertify first. The lock is released
+/// by dropping the descriptor.
+async fn inhibit_sleep(manager: &Proxy<'_>) -> Option<OwnedFd> {
+    let args = ("sleep", "alertify", "Recording the state for the resume report", "delay");
+    let result = async { manager.call_method("Inhibit", &args).await?.body().deserialize::<OwnedFd>() }.await;
+
+    result
+        .inspect_err(|e| warn!("Failed to take a sleep delay lock, the resume report may be inaccurate: {}", e))
+        .ok()
+}
+
+async fn battery_percentage(battery: Option<&BatteryBackend>) -> Option<f64> {
+    battery?.percentage().await.ok()
+}
+
+/// State captured when the system goes to sleep, for the resume report.
+#[derive(Debug, Clone, Copy)]
+struct Suspend {
+    // the monotonic clock stops during suspend, so the wall clock is used
+    since: SystemTime,
+    battery: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+struct ResumeReport {
+    slept: Option<Duration>,
+    battery_before: Option<f64>,
+    battery_after: Option<f64>,
+}
+
+impl ResumeReport {
+    fn new(suspend: Option<Suspend>, battery_after: Option<f64>) -> Self {
+        let Some(suspend) = suspend else {
+            return Self::default();
+        };
+
+        Self {
+            slept: SystemTime::now().duration_since(suspend.since).ok(),
+            battery_before: suspend.battery,
+            battery_after,
+        }
+    }
+
+    fn battery_lost(&self) -> Option<f64> {
+        Some(self.battery_before? - self.battery_after?)
+    }
+}
+
+async fn notify_event(rules: &[SessionRule], event: &str, report: &ResumeReport) -> Result<()> {
+    let percent = |value: Option<f64>| value.map(|v| format!("{:.0}", v)).unwrap_or_default();
+
+    for rule in rules.iter().filter(|rule| rule.event == event) {
+        let mut fields = HashMap::new();
+        fields.insert("event",          event.to_string());
+        fields.insert("duration",       report.slept.map(format_duration).unwrap_or_default());
+        fields.insert("duration_secs",  report.slept.map(|d| d.as_secs().to_string()).unwrap_or_default());
+        fields.insert("battery_before", percent(report.battery_before));
+        fields.insert("battery_after",  percent(report.battery_after));
+        fields.insert("battery_lost",   percent(report.battery_lost()));
+
+        let rule_clone = rule.clone();
+        let _ = execute_command(rule_clone.message.exec.as_ref());
+        task::spawn_blocking(move || {
+            let _ = rule_clone.message.notify(&fields);
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn monitor_session(
+    battery_backend: BatteryBackendKind,
+    rules: Vec<SessionRule>,
+    _sent: Arc<Mutex<HashSet<String>>>,
+) -> Result<()> {
+    // runs without rules as well, level monitors rely on the resume wake-up
+    let conn = Connection::system().await?;
+    let manager = Proxy::new(&conn, LOGIN_DESTINATION, LOGIN_PATH, MANAGER_INTERFACE).await?;
+
+    let mut sleeps = manager.receive_signal("PrepareForSleep").await?;
+    let mut shutdowns = manager.receive_signal("PrepareForShutdown").await?;
+    let watches_lid = rules.iter().any(|rule| rule.event == "lid_closed" || rule.event == "lid_opened");
+    let mut lid = if watches_lid {
+        Some(LidWatch::new(&conn).await?)
+    } else {
+        None
+    };
+
+    // alertify may run outside of a session, e.g. as a system service
+    let watches_lock = rules.iter().any(|rule| rule.event == "lock" || rule.event == "unlock");
+    let session = if watches_lock {
+        Some(session_proxy(&conn, &manager).await)
+    } else {
+        None
+    };
+    let (mut locks, mut unlocks) = match session {
+        None => (None, None),
+        Some(Ok(session)) => (
+            Some(session.receive_signal("Lock").await?),
+            Some(session.receive_signal("Unlock").await?),
+        ),
+        Some(Err(e)) => {
+            warn!("No logind session found, lock events are unavailable: {}", e);
+            (None, None)
+        }
+    };
+
+    let watches_sleep = rules.iter().any(|rule| rule.event == "sleep" || rule.event == "resume");
+    let battery = if watches_sleep {
+        BatteryBackend::new(battery_backend)
+            .await
+            .inspect_err(|e| warn!("Battery is unavailable for the resume report: {}", e))
+            .ok()
+    } else {
+        None
+    };
+    let mut inhibitor = if watches_sleep { inhibit_sleep(&manager).await } else { None };
+
+    let mut suspend: Option<Suspend> = None;
+    let no_report = ResumeReport::default();
+
+    loop {
+        tokio::select! {
+            Some(message) = sleeps.next() => {
+                if message.body().deserialize::<bool>()? {
+                    suspend = Some(Suspend {
+                        since: SystemTime::now(),
+                        battery: battery_percentage(battery.as_ref()).await,
+                    });
+                    // logind waits with the suspend until the sleep rules ran
+                    let result = notify_event(&rules, "sleep", &no_report).await;
+                    drop(inhibitor.take());
+                    result?;
+                } else {
+                    // the sent alerts are kept, edge and transition keys must
+                    // survive the suspend; level monitors re-sample right away
+                    // and duration based state is reset through ResumeWatch
+                    RESUME_COUNT.fetch_add(1, Ordering::Relaxed);
+                    RESUMED.notify_waiters();
+
+                    let report = ResumeReport::new(suspend.take(), battery_percentage(battery.as_ref()).await);
+                    notify_event(&rules, "resume", &report).await?;
+                    if watches_sleep {
+                        inhibitor = inhibit_sleep(&manager).await;
+                    }
+                }
+            }
+            Some(message) = shutdowns.next() => {
+                if message.body().deserialize::<bool>()? {
+                    notify_event(&rules, "shutdown", &no_report).await?;
+                }
+            }
+            Some(_) = async { locks.as_mut()?.next().await } => {
+                notify_event(&rules, "lock", &no_report).await?;
+            }
+            Some(_) = async { unlocks.as_mut()?.next().await } => {
+                notify_event(&rules, "unlock", &no_report).await?;
+            }
+            Some(changed) = async { Some(lid.as_mut()?.changed().await) } => {
+                match changed {
+                    Ok(Some(closed)) => {
+                        let event = if closed { "lid_closed" } else { "lid_opened" };
+                        notify_event(&rules, event, &no_report).await?;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        warn!("Failed to read the lid state, lid events are unavailable: {}", e);
+                        lid = None;
+                    }
+                }
+            }
+            else => return Ok(()),
+        }
+    }
+}
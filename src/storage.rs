@@ -2,9 +2,9 @@ use anyhow::Result;
 use humansize::{format_size, DECIMAL};
 use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}, time::Duration};
 use sysinfo::{DiskKind, Disks};
-use tokio::{task, time::sleep};
+use tokio::task;
 
-use crate::{config::StorageRule, utils::execute_command};
+use crate::{config::StorageRule, matcher::Matcher, session::interruptible_sleep, utils::execute_command};
 
 pub async fn monitor_storage(rules: Vec<StorageRule>, sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
     loop {
@@ -22,6 +22,18 @@ pub async fn monitor_storage(rules: Vec<StorageRule>, sent: Arc<Mutex<HashSet<St
                 let name         = disk.name().to_string_lossy().into_owned();
                 let fs           = disk.file_system().to_string_lossy().into_owned();
                 let mount        = disk.mount_point().to_string_lossy().into_owned();
+
+                let matches = |expect: &Option<Matcher>, actual: &str| {
+                    expect.as_ref().is_none_or(|matcher| matcher.matches(actual))
+                };
+                if !(matches(&rule.mount, &mount)
+                    && matches(&rule.name, &name)
+                    && matches(&rule.fs, &fs)
+                    && matches(&rule.kind, &kind))
+                {
+                    continue;
+                }
+
                 let total        = disk.total_space();
                 let left         = disk.available_space();
                 let used         = total - left;
@@ -73,6 +85,6 @@ pub async fn monitor_storage(rules: Vec<StorageRule>, sent: Arc<Mutex<HashSet<St
             }
         }
 
-        sleep(Duration::from_secs(60)).await;
+        interruptible_sleep(Duration::from_secs(60)).await;
     }
 }
@@ -0,0 +1,212 @@
+use anyhow::Result;
+use futures_util::{future::join, StreamExt};
+use log::{debug, warn};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+use tokio::task;
+use zbus::{
+    fdo::PropertiesProxy,
+    message::Type,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue},
+    Connection, MatchRule, MessageStream, Proxy,
+};
+use zbus_names::InterfaceName;
+
+use crate::{config::SystemdUnitRule, utils::execute_command};
+
+const SYSTEMD_DESTINATION: &str = "org.freedesktop.systemd1";
+const SYSTEMD_PATH: &str = "/org/freedesktop/systemd1";
+const SYSTEMD_UNIT_PATH: &str = "/org/freedesktop/systemd1/unit";
+const MANAGER_INTERFACE: &str = "org.freedesktop.systemd1.Manager";
+const UNIT_INTERFACE: &str = "org.freedesktop.systemd1.Unit";
+
+pub const SCOPES: &[&str] = &["system", "user"];
+
+#[derive(Debug, Default, Clone)]
+struct UnitFailure {
+    unit: String,
+    description: String,
+    result: String,
+    message: String,
+}
+
+/// Type specific interface of a unit, e.g. `org.freedesktop.systemd1.Service`
+/// for `foo.service`, which carries the `Result` property.
+fn type_interface(unit: &str) -> Option<String> {
+    let kind = unit.rsplit_once('.')?.1;
+    let mut chars = kind.chars();
+    let first = chars.next()?.to_ascii_uppercase();
+    Some(format!("org.freedesktop.systemd1.{}{}", first, chars.as_str()))
+}
+
+async fn unit_failure(conn: &Connection, path: ObjectPath<'_>, job_result: &str) -> Result<Option<UnitFailure>> {
+    let properties = PropertiesProxy::builder(conn)
+        .destination(SYSTEMD_DESTINATION)?
+        .path(path.into_owned())?
+        .build()
+        .await?;
+    let interface = InterfaceName::try_from(UNIT_INTERFACE)?;
+
+    let active_state = String::try_from(properties.get(interface.clone(), "ActiveState").await?)?;
+    if active_state != "failed" {
+        return Ok(None);
+    }
+
+    let unit = String::try_from(properties.get(interface.clone(), "Id").await?)?;
+    let description = String::try_from(properties.get(interface, "Description").await?)?;
+
+    let mut result = job_result.to_string();
+    let mut status = String::new();
+    if let Some(type_interface) = type_interface(&unit) {
+        let type_interface = InterfaceName::try_from(type_interface)?;
+        if let Ok(value) = properties.get(type_interface.clone(), "Result").await {
+            result = String::try_from(value)?;
+        }
+        if let Ok(value) = properties.get(type_interface, "StatusText").await {
+            status = String::try_from(value)?;
+        }
+    }
+
+    let message = if status.is_empty() {
+        format!("Failed with result '{}'.", result)
+    } else {
+        status
+    };
+
+    Ok(Some(UnitFailure { unit, description, result, message }))
+}
+
+fn rule_matches(rule: &SystemdUnitRule, scope: &str, failure: &UnitFailure) -> bool {
+    rule.scope.as_ref().is_none_or(|expect| expect == scope)
+        && rule.unit.as_ref().is_none_or(|matcher| matcher.matches(&failure.unit))
+        && rule.result.as_ref().is_none_or(|matcher| matcher.matches(&failure.result))
+}
+
+async fn notify_failure(scope: &str, failure: &UnitFailure, rules: &[SystemdUnitRule]) -> Result<()> {
+    for rule in rules.iter().filter(|rule| rule_matches(rule, scope, failure)) {
+        let mut fields = HashMap::new();
+        fields.insert("scope",       scope.to_string());
+        fields.insert("unit",        failure.unit.clone());
+        fields.insert("description", failure.description.clone());
+        fields.insert("result",      failure.result.clone());
+        fields.insert("message",     failure.message.clone());
+
+        let rule_clone = rule.clone();
+        let _ = execute_command(rule_clone.message.exec.as_ref());
+        task::spawn_blocking(move || {
+            let _ = rule_clone.message.notify(&fields);
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn monitor_manager(scope: &'static str, conn: Connection, rules: &[SystemdUnitRule], sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    let manager = Proxy::new(&conn, SYSTEMD_DESTINATION, SYSTEMD_PATH, MANAGER_INTERFACE).await?;
+    // systemd only emits unit signals while at least one client is subscribed
+    manager.call_method("Subscribe", &()).await?;
+
+    let mut jobs = manager.receive_signal("JobRemoved").await?;
+    let changes_rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .sender(SYSTEMD_DESTINATION)?
+        .interface("org.freedesktop.DBus.Properties")?
+        .member("PropertiesChanged")?
+        .path_namespace(SYSTEMD_UNIT_PATH)?
+        .build();
+    let mut changes = MessageStream::for_match_rule(changes_rule, &conn, None).await?;
+
+    loop {
+        let (path, job_result) = tokio::select! {
+            Some(message) = jobs.next() => {
+                let (_, _, unit, result): (u32, OwnedObjectPath, String, String) = message.body().deserialize()?;
+                if result == "done" || result == "skipped" {
+                    continue;
+                }
+
+                let Ok(reply) = manager.call_method("GetUnit", &(unit.as_str(),)).await else {
+                    continue;
+                };
+                (reply.body().deserialize::<OwnedObjectPath>()?, result)
+            }
+            Some(message) = changes.next() => {
+                let message = message?;
+                let (interface, changed, _): (String, HashMap<String, OwnedValue>, Vec<String>) = message.body().deserialize()?;
+                if interface != UNIT_INTERFACE {
+                    continue;
+                }
+                let Some(active_state) = changed.get("ActiveState") else {
+                    continue;
+                };
+                let Some(path) = message.header().path().map(|path| OwnedObjectPath::from(path.to_owned())) else {
+                    continue;
+                };
+
+                // leaving the failed state re-arms the notification
+                if String::try_from(active_state.try_clone()?)? != "failed" {
+                    let key = format!("systemd-unit-{}-{}", scope, path.as_str());
+                    sent.lock().unwrap().remove(&key);
+                    continue;
+                }
+
+                (path, String::new())
+            }
+            else => return Ok(()),
+        };
+
+        let failure = match unit_failure(&conn, path.as_ref(), &job_result).await {
+            Ok(Some(failure)) => failure,
+            Ok(None) => continue,
+            Err(e) => {
+                debug!("Failed to query unit {}: {}", path.as_str(), e);
+                continue;
+            }
+        };
+
+        let should_notify = {
+            let key = format!("systemd-unit-{}-{}", scope, path.as_str());
+            sent.lock().unwrap().insert(key)
+        };
+
+        if should_notify {
+            notify_failure(scope, &failure, rules).await?;
+        }
+    }
+}
+
+pub async fn monitor_systemd_units(rules: Vec<SystemdUnitRule>, sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let watches = |scope: &str| rules.iter().any(|rule| rule.scope.as_ref().is_none_or(|expect| expect == scope));
+
+    let system = async {
+        if watches("system") {
+            let result = match Connection::system().await {
+                Ok(conn) => monitor_manager("system", conn, &rules, sent.clone()).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                warn!("systemd system manager monitor failed: {}", e);
+            }
+        }
+    };
+    let user = async {
+        if watches("user") {
+            let result = match Connection::session().await {
+                Ok(conn) => monitor_manager("user", conn, &rules, sent.clone()).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                warn!("systemd user manager monitor failed: {}", e);
+            }
+        }
+    };
+
+    join(system, user).await;
+    Ok(())
+}
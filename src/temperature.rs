@@ -0,0 +1,248 @@
+use anyhow::Result;
+use std::{collections::{HashMap, HashSet}, fs, path::{Path, PathBuf}, sync::{Arc, Mutex}, time::Duration};
+use tokio::task;
+
+use crate::{config::TemperatureRule, session::interruptible_sleep, utils::execute_command};
+
+pub const HWMON_DIR: &str = "/sys/class/hwmon";
+const THERMAL_DIR: &str = "/sys/class/thermal";
+
+pub const LIMITS: &[&str] = &["crit", "max"];
+
+#[derive(Debug, Clone)]
+pub struct Sensor {
+    /// sysfs file the reading comes from, chip names like `nvme` or
+    /// `coretemp` repeat across devices while this stays unique
+    pub path: PathBuf,
+    pub chip: String,
+    pub sensor: String,
+    pub temp: f32,
+    pub crit: Option<f32>,
+    pub max: Option<f32>,
+}
+
+impl Sensor {
+    fn limit(&self, name: &str) -> Option<f32> {
+        match name {
+            "crit" => self.crit,
+            "max"  => self.max,
+            _      => None,
+        }
+    }
+}
+
+pub fn read_string(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+/// Reads a value reported in millidegrees Celsius.
+fn read_millidegrees(path: &Path) -> Option<f32> {
+    read_string(path)?.parse::<i64>().ok().map(|v| v as f32 / 1000.0)
+}
+
+pub fn read_hwmon_sensors(dir: &Path) -> Vec<Sensor> {
+    let Ok(chips) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut sensors = Vec::new();
+    for chip in chips.flatten() {
+        let chip_path = chip.path();
+        let chip_name = read_string(&chip_path.join("name"))
+            .unwrap_or_else(|| chip.file_name().to_string_lossy().into_owned());
+
+        let Ok(files) = fs::read_dir(&chip_path) else {
+            continue;
+        };
+
+        for file in files.flatten() {
+            let file_name = file.file_name().to_string_lossy().into_owned();
+            let Some(prefix) = file_name.strip_suffix("_input").filter(|p| p.starts_with("temp")) else {
+                continue;
+            };
+            let Some(temp) = read_millidegrees(&file.path()) else {
+                continue;
+            };
+
+            sensors.push(Sensor {
+                path: file.path(),
+                chip: chip_name.clone(),
+                sensor: read_string(&chip_path.join(format!("{}_label", prefix)))
+                    .unwrap_or_else(|| prefix.to_string()),
+                temp,
+                crit: read_millidegrees(&chip_path.join(format!("{}_crit", prefix))),
+                max: read_millidegrees(&chip_path.join(format!("{}_max", prefix))),
+            });
+        }
+    }
+
+    sensors
+}
+
+fn read_thermal_zones(dir: &Path) -> Vec<Sensor> {
+    let Ok(zones) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    zones
+        .flatten()
+        .filter(|zone| zone.file_name().to_string_lossy().starts_with("thermal_zone"))
+        .filter_map(|zone| {
+            let path = zone.path();
+            let temp = read_millidegrees(&path.join("temp"))?;
+
+            // the critical trip point acts as the zone's crit value
+            let crit = (0..)
+                .map_while(|i| {
+                    read_string(&path.join(format!("trip_point_{}_type", i)))
+                        .map(|kind| (i, kind))
+                })
+                .find(|(_, kind)| kind == "critical")
+                .and_then(|(i, _)| read_millidegrees(&path.join(format!("trip_point_{}_temp", i))));
+
+            Some(Sensor {
+                path: path.join("temp"),
+                chip: zone.file_name().to_string_lossy().into_owned(),
+                sensor: read_string(&path.join("type")).unwrap_or_default(),
+                temp,
+                crit,
+                max: None,
+            })
+        })
+        .collect()
+}
+
+pub async fn monitor_temperature(rules: Vec<TemperatureRule>, sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    loop {
+        let mut sensors = read_hwmon_sensors(Path::new(HWMON_DIR));
+        sensors.extend(read_thermal_zones(Path::new(THERMAL_DIR)));
+
+        for sensor in &sensors {
+            for (index, rule) in rules.iter().enumerate()
+                .filter(|(_, rule)| rule.chip.as_ref().is_none_or(|matcher| matcher.matches(&sensor.chip)))
+                .filter(|(_, rule)| rule.sensor.as_ref().is_none_or(|matcher| matcher.matches(&sensor.sensor)))
+            {
+                // a sensor limit takes precedence, level is the fallback
+                let Some(level) = rule.limit
+                    .as_deref()
+                    .and_then(|limit| sensor.limit(limit))
+                    .or(rule.level)
+                else {
+                    continue;
+                };
+
+                let should_notify = {
+                    let key = format!("temperature-{}-{}", index, sensor.path.display());
+                    let mut sent_guard = sent.lock().unwrap();
+
+                    if sensor.temp >= level {
+                        sent_guard.insert(key)
+                    } else {
+                        sent_guard.remove(&key);
+                        false
+                    }
+                };
+
+                if should_notify {
+                    let mut fields = HashMap::new();
+                    fields.insert("level",  level.to_string());
+                    fields.insert("chip",   sensor.chip.clone());
+                    fields.insert("sensor", sensor.sensor.clone());
+                    fields.insert("temp",   format!("{:.1}", sensor.temp));
+                    fields.insert("crit",   sensor.crit.map(|v| format!("{:.1}", v)).unwrap_or_default());
+                    fields.insert("max",    sensor.max.map(|v| format!("{:.1}", v)).unwrap_or_default());
+
+                    let rule_clone = rule.clone();
+                    let _ = execute_command(rule_clone.message.exec.as_ref());
+                    task::spawn_blocking(move || {
+                        let _ = rule_clone.message.notify(&fields);
+                    })
+                    .await?;
+                }
+            }
+        }
+
+        interruptible_sleep(Duration::from_secs(10)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, file: &str, value: &str) {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, format!("{}\n", value)).unwrap();
+    }
+
+    fn by_sensor(mut sensors: Vec<Sensor>) -> Vec<Sensor> {
+        sensors.sort_by(|a, b| (&a.chip, &a.sensor).cmp(&(&b.chip, &b.sensor)));
+        sensors
+    }
+
+    #[test]
+    fn hwmon_sensors() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "hwmon0/name", "coretemp");
+        write(dir.path(), "hwmon0/temp1_input", "54500");
+        write(dir.path(), "hwmon0/temp1_label", "Package id 0");
+        write(dir.path(), "hwmon0/temp1_crit", "100000");
+        write(dir.path(), "hwmon0/temp1_max", "80000");
+        write(dir.path(), "hwmon0/temp2_input", "-1250");
+        write(dir.path(), "hwmon0/fan1_input", "1200");
+        write(dir.path(), "hwmon0/in0_input", "1000");
+        // a sensor that can't be read right now is skipped
+        write(dir.path(), "hwmon0/temp3_input", "");
+        write(dir.path(), "hwmon1/temp1_input", "38000");
+
+        let sensors = by_sensor(read_hwmon_sensors(dir.path()));
+        assert_eq!(sensors.len(), 3);
+
+        assert_eq!(sensors[0].chip, "coretemp");
+        assert_eq!(sensors[0].sensor, "Package id 0");
+        assert_eq!(sensors[0].path, dir.path().join("hwmon0/temp1_input"));
+        assert_eq!(sensors[0].temp, 54.5);
+        assert_eq!(sensors[0].limit("crit"), Some(100.0));
+        assert_eq!(sensors[0].limit("max"), Some(80.0));
+
+        // without a label the sensor is named after its file
+        assert_eq!(sensors[1].sensor, "temp2");
+        assert_eq!(sensors[1].temp, -1.25);
+        assert_eq!(sensors[1].crit, None);
+
+        // chips without a name are named after their directory
+        assert_eq!(sensors[2].chip, "hwmon1");
+        assert_eq!(sensors[2].temp, 38.0);
+    }
+
+    #[test]
+    fn thermal_zones() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "thermal_zone0/type", "x86_pkg_temp");
+        write(dir.path(), "thermal_zone0/temp", "61000");
+        write(dir.path(), "thermal_zone0/trip_point_0_type", "passive");
+        write(dir.path(), "thermal_zone0/trip_point_0_temp", "95000");
+        write(dir.path(), "thermal_zone0/trip_point_1_type", "critical");
+        write(dir.path(), "thermal_zone0/trip_point_1_temp", "105000");
+        write(dir.path(), "thermal_zone1/type", "acpitz");
+        write(dir.path(), "thermal_zone1/temp", "27800");
+        write(dir.path(), "cooling_device0/type", "Processor");
+
+        let sensors = by_sensor(read_thermal_zones(dir.path()));
+        assert_eq!(sensors.len(), 2);
+
+        assert_eq!(sensors[0].chip, "thermal_zone0");
+        assert_eq!(sensors[0].sensor, "x86_pkg_temp");
+        assert_eq!(sensors[0].path, dir.path().join("thermal_zone0/temp"));
+        assert_eq!(sensors[0].temp, 61.0);
+        assert_eq!(sensors[0].crit, Some(105.0));
+
+        assert_eq!(sensors[1].temp, 27.8);
+        assert_eq!(sensors[1].crit, None);
+    }
+}
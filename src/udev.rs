@@ -1,40 +1,61 @@
 use anyhow::Result;
-use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}};
+use humansize::{format_size, DECIMAL};
+use log::{info, warn};
+use std::{collections::{BTreeSet, HashMap, HashSet}, sync::{Arc, Mutex}};
 use tokio::task;
 use tokio_stream::StreamExt;
-use tokio_udev::{AsyncMonitorSocket, EventType, MonitorBuilder, Device};
+use tokio_udev::{AsyncMonitorSocket, Device, Enumerator, EventType, MonitorBuilder};
 
-use crate::{config::{Config, PowerStatusRule}, utils::execute_command};
+use crate::{config::{Config, DeviceRule, PowerStatusRule}, display, matcher::Matcher, utils::execute_command};
+
+/// Subsystems the udev monitor subscribes to, `None` meaning all of them.
+/// Unless overridden in the config, device events are limited to the
+/// subsystems the rules refer to; a device rule without a literal `subsystem`
+/// needs every event. `power_supply` and `drm` are added whenever their rules
+/// exist, so an override only narrows the device events.
+fn subscribed_subsystems(rules: &Config) -> Option<BTreeSet<String>> {
+    let mut subsystems = BTreeSet::new();
+    if let Some(overridden) = &rules.udev_subsystems {
+        subsystems.extend(overridden.iter().cloned());
+    } else {
+        for rule in &rules.device {
+            let literals = rule.subsystem.as_ref().and_then(Matcher::literals)?;
+            subsystems.extend(literals.into_iter().map(str::to_string));
+        }
+    }
+    if !rules.power_supply.is_empty() {
+        subsystems.insert("power_supply".to_string());
+    }
+    if !rules.display.is_empty() {
+        subsystems.insert("drm".to_string());
+    }
+
+    Some(subsystems)
+}
 
 pub async fn listen_udev(rules: Config, sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
-    const ALLOW_SUBSYSTEMS: &[&str] = &[
-        "usb",
-        "block",
-        "net",
-        "input",
-        "sound",
-        "drm",
-        "tty",
-        "power_supply",
-        "video4linux",
-    ];
+    let subsystems = subscribed_subsystems(&rules);
 
     let mut monitor = MonitorBuilder::new()?;
-    for subsys in ALLOW_SUBSYSTEMS {
-        monitor = monitor.match_subsystem(subsys)?;
+    match &subsystems {
+        Some(subsystems) if subsystems.is_empty() => {
+            info!("No udev rules configured, not listening for device events");
+            return Ok(());
+        }
+        Some(subsystems) => {
+            info!("Listening for udev events of {}", subsystems.iter().cloned().collect::<Vec<_>>().join(", "));
+            for subsys in subsystems {
+                monitor = monitor.match_subsystem(subsys)?;
+            }
+        }
+        None => info!("Listening for udev events of all subsystems"),
     }
     let mut socket = AsyncMonitorSocket::new(monitor.listen()?)?;
 
-    while let Some(Ok(event)) = socket.next().await {
-        let initialized = event.is_initialized();
-        let subsystem = event
-            .subsystem()
-            .and_then(|s| s.to_str().map(str::to_string));
-        let sysname = event.sysname().to_str().map(str::to_string);
-        let sysnum = event.sysnum().map(|n| n as i32);
-        let devtype = event.devtype().and_then(|s| s.to_str().map(str::to_string));
-        let driver = event.driver().and_then(|s| s.to_str().map(str::to_string));
+    evaluate_startup_state(&rules, subsystems.as_ref(), &sent).await?;
+    let mut displays = display::scan_connectors();
 
+    while let Some(Ok(event)) = socket.next().await {
         let action = match event.event_type() {
             EventType::Add    => "add",
             EventType::Remove => "remove",
@@ -44,110 +65,351 @@ pub async fn listen_udev(rules: Config, sent: Arc<Mutex<HashSet<String>>>) -> Re
             _ => continue,
         };
 
-        if subsystem.clone().unwrap() == *"power_supply" && action == "change" {
-            let _ = handle_power_supply_change(event.clone(), rules.clone().power_supply, sent.clone()).await;
+        let device = event.device();
+        let subsystem = device.subsystem().and_then(|s| s.to_str());
+
+        if subsystem == Some("power_supply") {
+            match action {
+                "change" => {
+                    let _ = handle_power_supply_change(&device, &rules.power_supply, sent.clone(), false).await;
+                }
+                "remove" => {
+                    let supply = power_supply_name(&device, &power_supply_properties(&device));
+                    forget_power_supply(&supply, rules.power_supply.len(), &sent);
+                }
+                _ => {}
+            }
         }
 
-        for rule in rules.device.iter()
-            .filter(|rule| rule.action == action)
-            .filter(|rule| rule.initialized.is_none_or(|v| v == initialized))
-            .filter(|rule| match (&rule.subsystem, &subsystem) {
-                (None, _) | (_, None) => true,
-                (Some(expect), Some(actual)) => expect == actual,
-            })
-            .filter(|rule| match (&rule.sysname, &sysname) {
-                (None, _) | (_, None) => true,
-                (Some(expect), Some(actual)) => expect == actual,
-            })
-            .filter(|rule| rule.sysnum.is_none_or(|v| sysnum == Some(v)))
-            .filter(|rule| match (&rule.devtype, &devtype) {
-                (None, _) | (_, None) => true,
-                (Some(expect), Some(actual)) => expect == actual,
-            })
-            .filter(|rule| match (&rule.driver, &driver) {
-                (None, _) | (_, None) => true,
-                (Some(expect), Some(actual)) => expect == actual,
-            })
-        {
-            let mut fields = HashMap::new();
-            let subsystem = event.subsystem().and_then(|s| s.to_str().map(str::to_string));
-            let sysname   = event.sysname().to_str().map(str::to_string);
-            let sysnum    = event.sysnum().map(|n| n as i32);
-            let devtype   = event.devtype().and_then(|s| s.to_str().map(str::to_string));
-            let driver    = event.driver().and_then(|s| s.to_str().map(str::to_string));
-            let seq_num   = Some(event.sequence_number().to_string());
-            let devnum    = event.devnum().map(|n| n.to_string());
-            let syspath   = event.syspath().to_str().map(str::to_string);
-            let devpath   = event.devpath().to_str().map(str::to_string);
-            let devnode   = event.devnode().and_then(|s| s.to_str().map(str::to_string));
-
-            fields.insert("subsystem", subsystem);
-            fields.insert("sysname",   sysname);
-            fields.insert("sysnum",    sysnum.map(|n| n.to_string()));
-            fields.insert("devtype",   devtype);
-            fields.insert("driver",    driver);
-            fields.insert("seq_num",   seq_num);
-            fields.insert("devnum",    devnum.clone());
-            fields.insert("syspath",   syspath.clone());
-            fields.insert("devpath",   devpath);
-            fields.insert("devnode",   devnode);
-
-            let rule_clone = rule.clone();
-            let _ = execute_command(rule_clone.message.exec.as_ref());
-            task::spawn_blocking(move || {
-                let _ = rule_clone.message.notify(
-                    &fields
-                        .iter()
-                        .map(|(&k, v)| (k, v.clone().unwrap_or_default()))
-                        .collect(),
-                );
-            })
-            .await?;
+        if subsystem == Some("drm") && action == "change" {
+            if let Err(e) = display::handle_drm_change(&rules.display, &mut displays).await {
+                warn!("Failed to handle display change: {}", e);
+            }
         }
+
+        handle_device_event(&device, rules.device.iter(), action, Some(event.sequence_number())).await?;
     }
 
     Ok(())
 }
 
-async fn handle_power_supply_change(event: Device, rules: Vec<PowerStatusRule>, _sent: Arc<Mutex<HashSet<String>>>) -> Result<()> {
-    let name = event
-        .property_value("POWER_SUPPLY_NAME")
-        .and_then(|s| s.to_str())
-        .map(str::to_string);
-    let supply_type = event
-        .property_value("POWER_SUPPLY_TYPE")
-        .and_then(|s| s.to_str())
-        .map(str::to_string);
-    let online = event
-        .property_value("POWER_SUPPLY_ONLINE")
+fn enumerate_devices(subsystems: Option<&BTreeSet<String>>) -> Result<Vec<Device>> {
+    let mut enumerator = Enumerator::new()?;
+    for subsys in subsystems.into_iter().flatten() {
+        enumerator.match_subsystem(subsys)?;
+    }
+    Ok(enumerator.scan_devices()?.collect())
+}
+
+/// Evaluates rules against devices that were present before alertify started,
+/// treating each of them as a synthetic `add` event.
+async fn evaluate_startup_state(
+    rules: &Config,
+    subsystems: Option<&BTreeSet<String>>,
+    sent: &Arc<Mutex<HashSet<String>>>,
+) -> Result<()> {
+    let devices = enumerate_devices(subsystems)?;
+
+    for device in &devices {
+        if device.subsystem().and_then(|s| s.to_str()) == Some("power_supply") {
+            let _ = handle_power_supply_change(device, &rules.power_supply, sent.clone(), true).await;
+        }
+
+        let startup_rules = rules.device.iter().filter(|rule| rule.on_startup);
+        handle_device_event(device, startup_rules, "add", None).await?;
+    }
+
+    Ok(())
+}
+
+/// First non-empty udev property, falling back to sysfs attributes.
+fn device_value(device: &Device, properties: &[&str], attributes: &[&str]) -> Option<String> {
+    properties
+        .iter()
+        .filter_map(|name| device.property_value(name))
+        .chain(attributes.iter().filter_map(|name| device.attribute_value(name)))
+        .filter_map(|value| value.to_str())
+        .map(str::trim)
+        .find(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+fn device_vendor_id(device: &Device) -> Option<String> {
+    device_value(device, &["ID_VENDOR_ID"], &["idVendor"])
+}
+
+fn device_product_id(device: &Device) -> Option<String> {
+    device_value(device, &["ID_MODEL_ID"], &["idProduct"])
+}
+
+fn device_serial(device: &Device) -> Option<String> {
+    device_value(device, &["ID_SERIAL_SHORT", "ID_SERIAL"], &["serial"])
+}
+
+fn device_property(device: &Device, name: &str) -> Option<String> {
+    device.property_value(name).and_then(|v| v.to_str()).map(str::to_string)
+}
+
+fn device_attribute(device: &Device, name: &str) -> Option<String> {
+    device.attribute_value(name).and_then(|v| v.to_str()).map(|v| v.trim().to_string())
+}
+
+/// udev keeps tags in the `TAGS` and `CURRENT_TAGS` properties as `:a:b:`.
+fn device_tags(device: &Device) -> HashSet<String> {
+    ["TAGS", "CURRENT_TAGS"]
+        .iter()
+        .filter_map(|name| device_property(device, name))
+        .flat_map(|tags| tags.split(':').map(str::to_string).collect::<Vec<_>>())
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// A field the rule sets has to be reported by the device and match, unless
+/// the matcher accepts an absent value with `<missing>`.
+fn field_matches(expect: Option<&Matcher>, actual: Option<&str>) -> bool {
+    expect.is_none_or(|matcher| matcher.matches_value(actual))
+}
+
+fn device_rule_matches(rule: &DeviceRule, device: &Device) -> bool {
+    let initialized = device.is_initialized();
+    let subsystem = device.subsystem().and_then(|s| s.to_str());
+    let sysname = device.sysname().to_str();
+    let sysnum = device.sysnum().map(|n| n as i32);
+    let devtype = device.devtype().and_then(|s| s.to_str());
+    let driver = device.driver().and_then(|s| s.to_str());
+
+    rule.initialized.is_none_or(|v| v == initialized)
+        && field_matches(rule.subsystem.as_ref(), subsystem)
+        && field_matches(rule.sysname.as_ref(), sysname)
+        && rule.sysnum.is_none_or(|v| sysnum == Some(v))
+        && field_matches(rule.devtype.as_ref(), devtype)
+        && field_matches(rule.driver.as_ref(), driver)
+        && field_matches(rule.vendor_id.as_ref(), device_vendor_id(device).as_deref())
+        && field_matches(rule.product_id.as_ref(), device_product_id(device).as_deref())
+        && field_matches(rule.serial.as_ref(), device_serial(device).as_deref())
+        && rule.properties.iter().all(|(name, matcher)| matcher.matches_value(device_property(device, name).as_deref()))
+        && rule.attributes.iter().all(|(name, matcher)| matcher.matches_value(device_attribute(device, name).as_deref()))
+        && (rule.tags.is_empty() || {
+            let tags = device_tags(device);
+            rule.tags.iter().all(|tag| tags.contains(tag))
+        })
+}
+
+fn device_fields(device: &Device, seq_num: Option<u64>) -> HashMap<&'static str, Option<String>> {
+    let mut fields = HashMap::new();
+    let subsystem = device.subsystem().and_then(|s| s.to_str().map(str::to_string));
+    let sysname   = device.sysname().to_str().map(str::to_string);
+    let sysnum    = device.sysnum().map(|n| n as i32);
+    let devtype   = device.devtype().and_then(|s| s.to_str().map(str::to_string));
+    let driver    = device.driver().and_then(|s| s.to_str().map(str::to_string));
+    let devnum    = device.devnum().map(|n| n.to_string());
+    let syspath   = device.syspath().to_str().map(str::to_string);
+    let devpath   = device.devpath().to_str().map(str::to_string);
+    let devnode   = device.devnode().and_then(|s| s.to_str().map(str::to_string));
+
+    fields.insert("subsystem", subsystem);
+    fields.insert("sysname",   sysname);
+    fields.insert("sysnum",    sysnum.map(|n| n.to_string()));
+    fields.insert("devtype",   devtype);
+    fields.insert("driver",    driver);
+    fields.insert("seq_num",   seq_num.map(|n| n.to_string()));
+    fields.insert("devnum",    devnum);
+    fields.insert("syspath",   syspath);
+    fields.insert("devpath",   devpath);
+    fields.insert("devnode",   devnode);
+
+    // udev encodes spaces in vendor and model names as underscores
+    let name = |properties: &[&str], attributes: &[&str]| {
+        device_value(device, properties, attributes).map(|value| value.replace('_', " "))
+    };
+    // block devices report their size in 512 byte sectors
+    let size = device.attribute_value("size")
         .and_then(|s| s.to_str())
-        .map(str::to_string);
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .filter(|_| subsystem_is(device, "block"))
+        .map(|sectors| sectors * 512);
+
+    fields.insert("vendor",     name(&["ID_VENDOR", "ID_VENDOR_FROM_DATABASE"], &["manufacturer"]));
+    fields.insert("model",      name(&["ID_MODEL", "ID_MODEL_FROM_DATABASE"], &["product"]));
+    fields.insert("vendor_id",  device_vendor_id(device));
+    fields.insert("product_id", device_product_id(device));
+    fields.insert("serial",     device_serial(device));
+    fields.insert("fs_label",   device_value(device, &["ID_FS_LABEL"], &[]));
+    fields.insert("fs_type",    device_value(device, &["ID_FS_TYPE"], &[]));
+    fields.insert("fs_uuid",    device_value(device, &["ID_FS_UUID"], &[]));
+    fields.insert("size_bytes", size.map(|n| n.to_string()));
+    fields.insert("size",       size.map(|n| format_size(n, DECIMAL)));
+    fields
+}
 
-    for rule in rules.into_iter()
-        .filter(|rule| match (&rule.name, &name) {
-            (None, _) | (_, None) => true,
-            (Some(expect), Some(actual)) => expect == actual,
+/// Every udev property as `prop.NAME`, and the sysfs attributes the rule's
+/// templates refer to as `attr.NAME`, reading all of them could be slow.
+fn device_template_fields(device: &Device, rule: &DeviceRule) -> HashMap<String, String> {
+    let mut fields: HashMap<String, String> = device
+        .properties()
+        .filter_map(|prop| {
+            let name = prop.name().to_str()?;
+            let value = prop.value().to_str()?;
+            Some((format!("prop.{}", name), value.to_string()))
         })
-        .filter(|rule| match (&rule.supply_type, &supply_type) {
-            (None, _) | (_, None) => true,
-            (Some(expect), Some(actual)) => expect == actual,
+        .collect();
+
+    for placeholder in rule.message.placeholders() {
+        if let Some(name) = placeholder.strip_prefix("attr.") {
+            if let Some(value) = device_attribute(device, name) {
+                fields.insert(placeholder.to_string(), value);
+            }
+        }
+    }
+
+    fields
+}
+
+fn subsystem_is(device: &Device, subsystem: &str) -> bool {
+    device.subsystem().and_then(|s| s.to_str()) == Some(subsystem)
+}
+
+async fn handle_device_event<'a>(
+    device: &Device,
+    rules: impl Iterator<Item = &'a DeviceRule>,
+    action: &str,
+    seq_num: Option<u64>,
+) -> Result<()> {
+    for rule in rules.filter(|rule| rule.action == action && device_rule_matches(rule, device)) {
+        let fields = device_fields(device, seq_num);
+        let template_fields = device_template_fields(device, rule);
+
+        let rule_clone = rule.clone();
+        let _ = execute_command(rule_clone.message.exec.as_ref());
+        task::spawn_blocking(move || {
+            let _ = rule_clone.message.notify(
+                &fields
+                    .iter()
+                    .map(|(&k, v)| (k, v.clone().unwrap_or_default()))
+                    .chain(template_fields.iter().map(|(k, v)| (k.as_str(), v.clone())))
+                    .collect(),
+            );
         })
-        .filter(|rule| match (&rule.online, &online) {
-            (None, _) | (_, None) => true,
-            (Some(expect), Some(actual)) => expect == actual,
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Normalizes a `POWER_SUPPLY_*` property name to its template field name,
+/// e.g. `POWER_SUPPLY_VOLTAGE_NOW` becomes `voltage_now`.
+fn power_supply_key(name: &str) -> String {
+    let upper = name.to_uppercase();
+    upper
+        .strip_prefix("POWER_SUPPLY_")
+        .unwrap_or(&upper)
+        .to_lowercase()
+}
+
+fn power_supply_properties(event: &Device) -> HashMap<String, String> {
+    event
+        .properties()
+        .filter_map(|prop| {
+            let name = prop.name().to_str()?;
+            if !name.starts_with("POWER_SUPPLY_") {
+                return None;
+            }
+            let value = prop.value().to_str()?;
+            Some((power_supply_key(name), value.to_string()))
         })
-    {
-        let mut fields = HashMap::new();
-        fields.insert("name", name.clone());
-        fields.insert("type", supply_type.clone());
-        fields.insert("online", online.clone());
+        .collect()
+}
+
+fn power_supply_rule_matches(rule: &PowerStatusRule, props: &HashMap<String, String>) -> bool {
+    let value = |key: &str| props.get(key).map(String::as_str);
+
+    field_matches(rule.name.as_ref(), value("name"))
+        && field_matches(rule.supply_type.as_ref(), value("type"))
+        && field_matches(rule.online.as_ref(), value("online"))
+        && rule.properties.iter().all(|(key, matcher)| matcher.matches_value(value(&power_supply_key(key))))
+}
+
+/// Evaluates power supply rules for a change event. At startup the current
+/// state is only recorded for edge-triggered rules unless `on_startup` is set.
+fn power_supply_name(device: &Device, props: &HashMap<String, String>) -> String {
+    props
+        .get("name")
+        .cloned()
+        .or_else(|| device.sysname().to_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn power_supply_sent_key(index: usize, supply: &str) -> String {
+    format!("power_supply-{}-{}", index, supply)
+}
+
+/// Decides whether a rule notifies about a supply, edge-triggered rules only
+/// when they start matching it.
+fn power_supply_should_notify(
+    index: usize,
+    rule: &PowerStatusRule,
+    supply: &str,
+    matches: bool,
+    startup: bool,
+    sent: &Mutex<HashSet<String>>,
+) -> bool {
+    let key = power_supply_sent_key(index, supply);
+
+    if startup {
+        if matches && rule.edge {
+            sent.lock().unwrap().insert(key);
+        }
+        matches && rule.on_startup
+    } else if rule.edge {
+        let mut sent_guard = sent.lock().unwrap();
+
+        if matches {
+            sent_guard.insert(key)
+        } else {
+            sent_guard.remove(&key);
+            false
+        }
+    } else {
+        matches
+    }
+}
+
+/// Drops the edge state of a removed supply, so that its rules fire again
+/// when it's plugged back in.
+fn forget_power_supply(supply: &str, rules: usize, sent: &Mutex<HashSet<String>>) {
+    let mut sent_guard = sent.lock().unwrap();
+    for index in 0..rules {
+        sent_guard.remove(&power_supply_sent_key(index, supply));
+    }
+}
 
-        let _ = execute_command(rule.message.exec.as_ref());
+async fn handle_power_supply_change(
+    device: &Device,
+    rules: &[PowerStatusRule],
+    sent: Arc<Mutex<HashSet<String>>>,
+    startup: bool,
+) -> Result<()> {
+    let props = power_supply_properties(device);
+    let supply = power_supply_name(device, &props);
+
+    for (index, rule) in rules.iter().enumerate() {
+        let matches = power_supply_rule_matches(rule, &props);
+        if !power_supply_should_notify(index, rule, &supply, matches, startup, &sent) {
+            continue;
+        }
+
+        let mut fields = props.clone();
+        for key in ["name", "type", "online"] {
+            fields.entry(key.to_string()).or_default();
+        }
+
+        let rule_clone = rule.clone();
+        let _ = execute_command(rule_clone.message.exec.as_ref());
         task::spawn_blocking(move || {
-            let _ = rule.message.notify(
+            let _ = rule_clone.message.notify(
                 &fields
                     .iter()
-                    .map(|(&k, v)| (k, v.clone().unwrap_or_default()))
+                    .map(|(k, v)| (k.as_str(), v.clone()))
                     .collect(),
             );
         })
@@ -156,3 +418,105 @@ async fn handle_power_supply_change(event: Device, rules: Vec<PowerStatusRule>,
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(sources: &[&str]) -> Matcher {
+        Matcher::new(sources).unwrap()
+    }
+
+    #[test]
+    fn field_matching_table() {
+        let usb_storage = matcher(&["usb-storage"]);
+        let optional = matcher(&["usb-storage", "<missing>"]);
+        let missing = matcher(&["<missing>"]);
+
+        let cases = [
+            (None,               None,                true),
+            (None,               Some("uas"),         true),
+            (Some(&usb_storage), Some("usb-storage"), true),
+            (Some(&usb_storage), Some("uas"),         false),
+            // a rule field never matches a device that doesn't report it
+            (Some(&usb_storage), None,                false),
+            (Some(&optional),    None,                true),
+            (Some(&optional),    Some("usb-storage"), true),
+            (Some(&optional),    Some("uas"),         false),
+            (Some(&missing),     None,                true),
+            (Some(&missing),     Some("usb-storage"), false),
+        ];
+
+        for (expect, actual, expected) in cases {
+            assert_eq!(field_matches(expect, actual), expected, "{:?} against {:?}", expect.map(ToString::to_string), actual);
+        }
+    }
+
+    #[test]
+    fn subsystem_override_keeps_power_supply_and_drm() {
+        let mut rules = Config {
+            udev_subsystems: Some(vec!["usb".to_string()]),
+            device: vec![DeviceRule::default()],
+            ..Default::default()
+        };
+
+        let subsystems = subscribed_subsystems(&rules).unwrap();
+        assert_eq!(subsystems.into_iter().collect::<Vec<_>>(), ["drm", "power_supply", "usb"]);
+
+        rules.power_supply.clear();
+        rules.display.clear();
+        let subsystems = subscribed_subsystems(&rules).unwrap();
+        assert_eq!(subsystems.into_iter().collect::<Vec<_>>(), ["usb"]);
+
+        // without the override, a rule without a subsystem needs every event
+        rules.udev_subsystems = None;
+        assert_eq!(subscribed_subsystems(&rules), None);
+    }
+
+    #[test]
+    fn power_supply_edge_after_remove() {
+        let sent = Mutex::new(HashSet::new());
+        let rule = PowerStatusRule::default();
+        let notify = |matches| power_supply_should_notify(0, &rule, "ucsi-source-psy-USBC000:001", matches, false, &sent);
+
+        assert!(notify(true));
+        assert!(!notify(true));
+
+        // unplugged while matching, no change event clears the state
+        forget_power_supply("ucsi-source-psy-USBC000:001", 1, &sent);
+        assert!(sent.lock().unwrap().is_empty());
+        assert!(notify(true));
+        assert!(!notify(true));
+        assert!(!notify(false));
+        assert!(notify(true));
+    }
+
+    #[test]
+    fn power_supply_matching() {
+        let props: HashMap<String, String> = [("name", "BAT0"), ("type", "Battery"), ("status", "Discharging")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        let rule = |configure: fn(&mut PowerStatusRule)| {
+            let mut rule = PowerStatusRule::default();
+            configure(&mut rule);
+            power_supply_rule_matches(&rule, &props)
+        };
+
+        assert!(rule(|_| {}));
+        assert!(rule(|r| r.supply_type = Some(matcher(&["Battery"]))));
+        assert!(!rule(|r| r.supply_type = Some(matcher(&["Mains"]))));
+        assert!(!rule(|r| r.online = Some(matcher(&["1"]))));
+        assert!(rule(|r| r.online = Some(matcher(&["1", "<missing>"]))));
+        assert!(rule(|r| {
+            r.properties.insert("POWER_SUPPLY_STATUS".to_string(), matcher(&["Discharging"]));
+        }));
+        assert!(!rule(|r| {
+            r.properties.insert("capacity_level".to_string(), matcher(&["Low"]));
+        }));
+        assert!(rule(|r| {
+            r.properties.insert("capacity_level".to_string(), matcher(&["<missing>"]));
+        }));
+    }
+}
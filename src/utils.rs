@@ -1,4 +1,6 @@
 use notify_rust::Urgency;
+use regex::{Captures, Regex};
+use std::{collections::HashMap, time::{Duration, Instant}};
 use tokio::process::Command;
 use log::debug;
 use anyhow::{Context, Result};
@@ -31,3 +33,110 @@ pub fn execute_command(command: Option<&String>) -> Result<()> {
 
     Ok(())
 }
+
+/// Matches `text` against a shell-style pattern supporting `*` and `?`.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Collapses a burst of matches into one notification per `interval`.
+#[derive(Debug, Default)]
+struct RateLimit {
+    last_notified: Option<Instant>,
+    matches: u64,
+}
+
+impl RateLimit {
+    /// Records a match, returns the number of matches since the previous
+    /// notification when a notification should be sent now.
+    fn hit(&mut self, interval: Duration) -> Option<u64> {
+        self.matches += 1;
+
+        let now = Instant::now();
+        if self.last_notified.is_some_and(|last| now.duration_since(last) < interval) {
+            return None;
+        }
+
+        self.last_notified = Some(now);
+        Some(std::mem::take(&mut self.matches))
+    }
+}
+
+/// Rate limits of the rules of a log monitor, indexed like the rules, so that
+/// a flood of matching messages results in a single notification.
+#[derive(Debug)]
+pub struct RuleRateLimits {
+    limits: Vec<RateLimit>,
+}
+
+impl RuleRateLimits {
+    pub fn new(rules: usize) -> Self {
+        Self { limits: (0..rules).map(|_| RateLimit::default()).collect() }
+    }
+
+    /// Records a match of rule `index` with an interval in seconds, returns
+    /// the number of matches the notification stands for when it should be
+    /// sent now.
+    pub fn hit(&mut self, index: usize, interval: u64) -> Option<u64> {
+        self.limits[index].hit(Duration::from_secs(interval))
+    }
+}
+
+/// Exposes the capture groups of a match as `1`, `2`, ... and by group name.
+pub fn capture_fields(regex: &Regex, caps: &Captures) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    for (i, name) in regex.capture_names().enumerate().skip(1) {
+        let value = caps.get(i).map(|m| m.as_str().to_string()).unwrap_or_default();
+        if let Some(name) = name {
+            fields.insert(name.to_string(), value.clone());
+        }
+        fields.insert(i.to_string(), value);
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limits_count_suppressed_matches() {
+        let mut limits = RuleRateLimits::new(2);
+
+        assert_eq!(limits.hit(0, 60), Some(1));
+        assert_eq!(limits.hit(0, 60), None);
+        assert_eq!(limits.hit(0, 60), None);
+        // rules are limited independently
+        assert_eq!(limits.hit(1, 60), Some(1));
+        // without an interval every match notifies, reporting the suppressed ones
+        assert_eq!(limits.hit(0, 0), Some(3));
+        assert_eq!(limits.hit(0, 0), Some(1));
+    }
+}